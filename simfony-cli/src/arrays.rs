@@ -0,0 +1,172 @@
+//! Map, fold and zip helpers for arrays of any size, which the preprocessor synthesizes
+//! for the `DEFINE_MAP_ARR_ANY`, `DEFINE_FOLD_ARR_ANY` and `DEFINE_ZIP_ARR_ANY` macros.
+//!
+//! The helper for size `N` splits the array the way SimplicityHL lays it out: the left part
+//! is the largest power of two below `N`, and each part is handled by the helper of its size.
+
+/// Kind of array helper.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArrayHelper {
+    /// `DEFINE_MAP_ARR_ANY(T1, T2, CTX, F, N)` defines `F_map_arr_N`,
+    /// which maps `[T1; N]` to `[T2; N]` with `F: (T1, CTX) -> (T2, CTX)`.
+    Map,
+    /// `DEFINE_FOLD_ARR_ANY(T, ACC, F, N)` defines `F_fold_arr_N`,
+    /// which folds `[T; N]` with `F: (T, ACC) -> ACC`.
+    Fold,
+    /// `DEFINE_ZIP_ARR_ANY(PREFIX, T1, T2, N)` defines `PREFIX_zip_arr_N`,
+    /// which zips `[T1; N]` and `[T2; N]` into `[(T1, T2); N]`.
+    Zip,
+}
+
+impl ArrayHelper {
+    /// Returns the helper that a built-in macro defines.
+    pub fn from_macro(name: &str) -> Option<Self> {
+        match name {
+            "DEFINE_MAP_ARR_ANY" => Some(Self::Map),
+            "DEFINE_FOLD_ARR_ANY" => Some(Self::Fold),
+            "DEFINE_ZIP_ARR_ANY" => Some(Self::Zip),
+            _ => None,
+        }
+    }
+
+    /// Number of arguments of the macro, including the size.
+    pub fn arity(self) -> usize {
+        match self {
+            Self::Map => 5,
+            Self::Fold | Self::Zip => 4,
+        }
+    }
+
+    /// Name of the helper for arrays of size `n`, given the arguments without the size.
+    pub fn name(self, args: &[String], n: usize) -> String {
+        match self {
+            Self::Map => format!("{}_map_arr_{}", args[3], n),
+            Self::Fold => format!("{}_fold_arr_{}", args[2], n),
+            Self::Zip => format!("{}_zip_arr_{}", args[0], n),
+        }
+    }
+
+    /// Source code of the helper for arrays of size `n`, given the arguments without the size.
+    /// The helpers of the parts must be defined before it.
+    pub fn define(self, args: &[String], n: usize) -> String {
+        let name = self.name(args, n);
+        if n == 1 {
+            return match self {
+                Self::Map => {
+                    let [t1, t2, ctx, f] = [&args[0], &args[1], &args[2], &args[3]];
+                    format!(
+                        "fn {name}(arr: [{t1}; 1], ctx: {ctx}) -> ([{t2}; 1], {ctx}) {{ \
+                         let elt: {t1} = <[{t1}; 1]>::into(arr); \
+                         let (res, ctx): ({t2}, {ctx}) = {f}(elt, ctx); \
+                         ([res], ctx) }}"
+                    )
+                }
+                Self::Fold => {
+                    let [t, acc, f] = [&args[0], &args[1], &args[2]];
+                    format!(
+                        "fn {name}(arr: [{t}; 1], acc: {acc}) -> {acc} {{ \
+                         let elt: {t} = <[{t}; 1]>::into(arr); \
+                         {f}(elt, acc) }}"
+                    )
+                }
+                Self::Zip => {
+                    let [t1, t2] = [&args[1], &args[2]];
+                    format!(
+                        "fn {name}(arr1: [{t1}; 1], arr2: [{t2}; 1]) -> [({t1}, {t2}); 1] {{ \
+                         let elt1: {t1} = <[{t1}; 1]>::into(arr1); \
+                         let elt2: {t2} = <[{t2}; 1]>::into(arr2); \
+                         [(elt1, elt2)] }}"
+                    )
+                }
+            };
+        }
+
+        let (l, r) = split(n);
+        let (left, right) = (self.name(args, l), self.name(args, r));
+        match self {
+            Self::Map => {
+                let [t1, t2, ctx] = [&args[0], &args[1], &args[2]];
+                format!(
+                    "fn {name}(arr: [{t1}; {n}], ctx: {ctx}) -> ([{t2}; {n}], {ctx}) {{ \
+                     let (arr1, arr2): ([{t1}; {l}], [{t1}; {r}]) = <[{t1}; {n}]>::into(arr); \
+                     let (res1, ctx): ([{t2}; {l}], {ctx}) = {left}(arr1, ctx); \
+                     let (res2, ctx): ([{t2}; {r}], {ctx}) = {right}(arr2, ctx); \
+                     let res: [{t2}; {n}] = <([{t2}; {l}], [{t2}; {r}])>::into((res1, res2)); \
+                     (res, ctx) }}"
+                )
+            }
+            Self::Fold => {
+                let [t, acc] = [&args[0], &args[1]];
+                format!(
+                    "fn {name}(arr: [{t}; {n}], acc: {acc}) -> {acc} {{ \
+                     let (arr0, arr1): ([{t}; {l}], [{t}; {r}]) = <[{t}; {n}]>::into(arr); \
+                     let acc: {acc} = {left}(arr0, acc); \
+                     let acc: {acc} = {right}(arr1, acc); \
+                     acc }}"
+                )
+            }
+            Self::Zip => {
+                let [t1, t2] = [&args[1], &args[2]];
+                format!(
+                    "fn {name}(arr1: [{t1}; {n}], arr2: [{t2}; {n}]) -> [({t1}, {t2}); {n}] {{ \
+                     let (arr1_1, arr1_2): ([{t1}; {l}], [{t1}; {r}]) = <[{t1}; {n}]>::into(arr1); \
+                     let (arr2_1, arr2_2): ([{t2}; {l}], [{t2}; {r}]) = <[{t2}; {n}]>::into(arr2); \
+                     let res_1: [({t1}, {t2}); {l}] = {left}(arr1_1, arr2_1); \
+                     let res_2: [({t1}, {t2}); {r}] = {right}(arr1_2, arr2_2); \
+                     <([({t1}, {t2}); {l}], [({t1}, {t2}); {r}])>::into((res_1, res_2)) }}"
+                )
+            }
+        }
+    }
+}
+
+/// Sizes of the two parts of an array of size `n` of at least 2.
+pub fn split(n: usize) -> (usize, usize) {
+    let left = n.next_power_of_two() / 2;
+    (left, n - left)
+}
+
+/// Sizes of the helpers that the helper for size `n` calls, directly or not,
+/// including `n` itself, smallest first.
+pub fn sizes(n: usize) -> Vec<usize> {
+    let mut sizes = vec![n];
+    let mut index = 0;
+    while index < sizes.len() {
+        if sizes[index] > 1 {
+            let (left, right) = split(sizes[index]);
+            for size in [left, right] {
+                if !sizes.contains(&size) {
+                    sizes.push(size);
+                }
+            }
+        }
+        index += 1;
+    }
+    sizes.sort_unstable();
+    sizes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_like_simplicityhl() {
+        assert_eq!(split(2), (1, 1));
+        assert_eq!(split(3), (2, 1));
+        assert_eq!(split(8), (4, 4));
+        assert_eq!(split(20), (16, 4));
+        assert_eq!(sizes(20), [1, 2, 4, 8, 16, 20]);
+        assert_eq!(sizes(7), [1, 2, 3, 4, 7]);
+
+        let args = ["u32", "u32", "sum"].map(str::to_string);
+        assert_eq!(
+            ArrayHelper::Fold.define(&args, 3),
+            "fn sum_fold_arr_3(arr: [u32; 3], acc: u32) -> u32 { \
+             let (arr0, arr1): ([u32; 2], [u32; 1]) = <[u32; 3]>::into(arr); \
+             let acc: u32 = sum_fold_arr_2(arr0, acc); \
+             let acc: u32 = sum_fold_arr_1(arr1, acc); \
+             acc }"
+        );
+    }
+}
@@ -0,0 +1,105 @@
+//! Assembly of programs from the Simplicity human encoding.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use simplicity::dag::{DagLike, InternalSharing};
+use simplicity::human_encoding::Forest;
+use simplicity::jet::Elements;
+use simplicity::node::Inner;
+use simplicity::{BitIter, Value};
+
+/// Parses and type-checks a program in the human encoding.
+/// Returns the encoded program, and the encoded witness if witness values are given.
+///
+/// Witness values are a JSON object that maps the names of the witness nodes
+/// to their Bit Machine representation in hex, padded with zeros to whole bytes.
+pub fn assemble(source: &str, witness: Option<&str>) -> Result<(Vec<u8>, Option<Vec<u8>>)> {
+    let forest = Forest::<Elements>::parse(source).map_err(|e| anyhow!("{}", e))?;
+    let main = forest
+        .roots()
+        .get("main")
+        .ok_or(anyhow!("Program has no `main` root"))?;
+
+    let Some(witness) = witness else {
+        return Ok((main.to_commit_node().encode_to_vec(), None));
+    };
+
+    let hex_values: HashMap<String, String> =
+        serde_json::from_str(witness).with_context(|| "Failed to parse witness")?;
+    let mut values: HashMap<Arc<str>, Value> = HashMap::new();
+    for item in main.post_order_iter::<InternalSharing>() {
+        if !matches!(item.node.inner(), Inner::Witness(_)) {
+            continue;
+        }
+        let name = item.node.name();
+        let hex_value = hex_values
+            .get(name.as_ref())
+            .ok_or(anyhow!("Missing witness value `{}`", name))?;
+        let bytes = hex::decode(hex_value.trim_start_matches("0x"))
+            .with_context(|| format!("Witness value `{}` is not hex", name))?;
+        let ty = &item.node.arrow().target;
+        let size = ty.bit_width().div_ceil(8);
+        if bytes.len() > size {
+            return Err(anyhow!(
+                "Witness value `{}` has {} bytes, but type {} takes {}",
+                name,
+                bytes.len(),
+                ty,
+                size
+            ));
+        }
+        let value = Value::from_padded_bits(&mut BitIter::from(bytes.into_iter()), ty)
+            .map_err(|_| anyhow!("Witness value `{}` is too short for type {}", name, ty))?;
+        values.insert(name.clone(), value);
+    }
+
+    let node = forest
+        .to_witness_node(&values)
+        .ok_or(anyhow!("Program has no `main` root"))?
+        .finalize_unpruned()
+        .map_err(|e| anyhow!("Failed to finalize program: {}", e))?;
+    let (program_bytes, witness_bytes) = node.encode_to_vec();
+    Ok((program_bytes, Some(witness_bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simfony::{Arguments, CompiledProgram};
+    use simplicity::node::CommitNode;
+
+    #[test]
+    fn assemble_serialized_program() {
+        let source = "fn main() {\n    let (_, sum): (bool, u32) = jet::add_32(1, 2);\n    assert!(jet::eq_32(sum, 3));\n}\n";
+        let compiled = CompiledProgram::new(source, Arguments::default(), false).unwrap();
+        let commit = compiled.commit();
+        let text = Forest::<Elements>::from_program(commit.clone()).string_serialize();
+
+        let (program, witness) = assemble(&text, None).unwrap();
+        assert_eq!(witness, None);
+        assert_eq!(program, commit.encode_to_vec());
+        let decoded = CommitNode::<Elements>::decode(BitIter::from(program.into_iter())).unwrap();
+        assert_eq!(decoded.cmr(), commit.cmr());
+    }
+
+    #[test]
+    fn check_witness_sizes() {
+        let source = "\
+wit1 := witness : 1 -> 2^32
+wit2 := witness : 1 -> 2^32
+wits_are_equal := comp (pair wit1 wit2) jet_eq_32 : 1 -> 2
+main := comp wits_are_equal jet_verify : 1 -> 1
+";
+        let witness = |wit2: &str| format!(r#"{{"wit1": "0x00000001", "wit2": "{}"}}"#, wit2);
+        let (_, encoded) = assemble(source, Some(&witness("0x00000001"))).unwrap();
+        assert!(encoded.is_some());
+        let error = assemble(source, Some(&witness("0x0000000100"))).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Witness value `wit2` has 5 bytes, but type 2^32 takes 4"
+        );
+        assert!(assemble(source, Some(&witness("0x000001"))).is_err());
+    }
+}
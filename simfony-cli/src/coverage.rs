@@ -0,0 +1,375 @@
+//! Branch coverage of the case nodes, reported in the lcov format.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+
+use simfony::debug::DebugSymbols;
+use simfony::CompiledProgram;
+
+use simplicity::bit_machine::ExecTracker;
+use simplicity::dag::{DagLike, InternalSharing};
+use simplicity::ffi::ffi::UWORD;
+use simplicity::jet::Elements;
+use simplicity::node::Inner;
+use simplicity::{Cmr, Ihr, RedeemNode};
+
+use simfony_cli::source_map::{Location, Preprocessed};
+
+use crate::tracker::CallLocator;
+
+/// Tracker that counts the branches taken by each case node and forwards the execution
+/// to another tracker.
+pub struct BranchRecorder<'a, T: ExecTracker<Elements>> {
+    inner: &'a mut T,
+    /// Number of times the left and the right branch of a case node ran
+    counts: HashMap<Ihr, [u64; 2]>,
+}
+
+impl<'a, T: ExecTracker<Elements>> BranchRecorder<'a, T> {
+    pub fn new(inner: &'a mut T) -> Self {
+        Self {
+            inner,
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Returns the number of times the left and the right branch of each case node ran.
+    pub fn counts(&self) -> &HashMap<Ihr, [u64; 2]> {
+        &self.counts
+    }
+}
+
+impl<'a, T: ExecTracker<Elements>> ExecTracker<Elements> for BranchRecorder<'a, T> {
+    fn track_left(&mut self, ihr: Ihr) {
+        self.counts.entry(ihr).or_default()[0] += 1;
+        self.inner.track_left(ihr);
+    }
+
+    fn track_right(&mut self, ihr: Ihr) {
+        self.counts.entry(ihr).or_default()[1] += 1;
+        self.inner.track_right(ihr);
+    }
+
+    fn track_jet_call(
+        &mut self,
+        jet: &Elements,
+        input_buffer: &[UWORD],
+        output_buffer: &[UWORD],
+        success: bool,
+    ) {
+        self.inner
+            .track_jet_call(jet, input_buffer, output_buffer, success);
+    }
+
+    fn track_dbg_call(&mut self, cmr: &Cmr, value: simplicity::Value) {
+        self.inner.track_dbg_call(cmr, value);
+    }
+}
+
+/// A branch of a case node, located in the original source code.
+struct Branch {
+    location: Location,
+    /// Whether the location is of a tracked call that occurs on a single line
+    exact: bool,
+    taken: u64,
+}
+
+/// Location of the first tracked call in a subtree.
+#[derive(Clone, Copy)]
+struct CallLocation<'a> {
+    location: &'a Location,
+    exact: bool,
+}
+
+/// Branch coverage accumulated over the executions of one or more programs.
+///
+/// The compiler does not record source spans of case nodes, so each branch is located
+/// by the first tracked call (a jet, an assertion, an unwrap or a `dbg!`) it contains
+/// whose text occurs on a single line. A branch whose calls all occur on several lines
+/// is reported at the first of them, and a branch without calls at `fn main`;
+/// such branches are counted by [`Coverage::unmapped`].
+#[derive(Default)]
+pub struct Coverage {
+    /// Branches keyed by the IHR of the case node and the side (left = false)
+    branches: HashMap<(Ihr, bool), Branch>,
+}
+
+impl Coverage {
+    /// Adds the branches of a compiled program and the number of times they ran.
+    ///
+    /// The branches are located in the unpruned program,
+    /// so the branches that never ran are reported as well.
+    pub fn add(
+        &mut self,
+        program: &Preprocessed,
+        compiled: &CompiledProgram,
+        redeem: &RedeemNode<Elements>,
+        debug_symbols: &DebugSymbols,
+        counts: &HashMap<Ihr, [u64; 2]>,
+    ) {
+        let mut locator = CallLocator::new(program);
+        let main = program
+            .functions()
+            .into_iter()
+            .find(|function| function.name == "main")
+            .map_or(1, |function| function.start);
+        let fallback = locator.location(main).map(|location| CallLocation {
+            location,
+            exact: false,
+        });
+        let commit = compiled.commit();
+        // Location of the first tracked call in the subtree of each node,
+        // preferring a call that occurs on a single line
+        let mut first_locations: Vec<Option<CallLocation>> = Vec::new();
+        // Locations of the branches of each case node
+        let mut case_locations: HashMap<Cmr, [Option<CallLocation>; 2]> = HashMap::new();
+        for item in commit.as_ref().post_order_iter::<InternalSharing>() {
+            let cmr = item.node.cmr();
+            let location = debug_symbols.get(&cmr).and_then(|call| {
+                let (line, exact) = match locator.line(&cmr, call.text()) {
+                    Some(line) => (line, true),
+                    None => (locator.first_line(&cmr, call.text())?, false),
+                };
+                let location = locator.location(line)?;
+                Some(CallLocation { location, exact })
+            });
+            let branch = |index: Option<usize>| index.and_then(|index| first_locations[index]);
+            let (left, right) = (branch(item.left_index), branch(item.right_index));
+            if matches!(item.node.inner(), Inner::Case(..)) {
+                case_locations.insert(cmr, [left.or(fallback), right.or(fallback)]);
+            }
+            let candidates = [location, left, right];
+            let exact = candidates.into_iter().flatten().find(|call| call.exact);
+            first_locations.push(exact.or_else(|| candidates.into_iter().flatten().next()));
+        }
+
+        // Case nodes with the same CMR but different types run as different nodes,
+        // and a pruned case node is an assertion with the CMR of the case node
+        let mut case_ihrs = HashSet::new();
+        for item in redeem.post_order_iter::<InternalSharing>() {
+            if !matches!(
+                item.node.inner(),
+                Inner::Case(..) | Inner::AssertL(..) | Inner::AssertR(..)
+            ) {
+                continue;
+            }
+            let Some(locations) = case_locations.get(&item.node.cmr()) else {
+                continue;
+            };
+            let ihr = item.node.ihr();
+            if !case_ihrs.insert(ihr) {
+                continue;
+            }
+            let taken = counts.get(&ihr).copied().unwrap_or_default();
+            for (side, location) in locations.iter().enumerate() {
+                let Some(location) = location else {
+                    continue;
+                };
+                self.branches
+                    .entry((ihr, side == 1))
+                    .or_insert(Branch {
+                        location: location.location.clone(),
+                        exact: location.exact,
+                        taken: 0,
+                    })
+                    .taken += taken[side];
+            }
+        }
+    }
+
+    /// Adds the branches of another coverage.
+    pub fn merge(&mut self, other: Coverage) {
+        for (key, branch) in other.branches {
+            self.branches
+                .entry(key)
+                .and_modify(|existing| existing.taken += branch.taken)
+                .or_insert(branch);
+        }
+    }
+
+    /// Returns the number of branches that are not located by a call on a single line.
+    pub fn unmapped(&self) -> usize {
+        self.branches
+            .values()
+            .filter(|branch| !branch.exact)
+            .count()
+    }
+
+    /// Writes the coverage as an lcov tracefile, with a record per source file.
+    ///
+    /// Every branch is reported on the line of its first tracked call,
+    /// which is also reported as a line executed as often as the branch.
+    pub fn write_lcov<W: Write>(&self, output: &mut W) -> std::io::Result<()> {
+        // Case nodes are numbered per file in the order of their first branch
+        let mut files: BTreeMap<&Path, Vec<(usize, Ihr, bool, u64)>> = BTreeMap::new();
+        for ((ihr, right), branch) in &self.branches {
+            files
+                .entry(branch.location.file.as_ref())
+                .or_default()
+                .push((branch.location.line, *ihr, *right, branch.taken));
+        }
+
+        writeln!(output, "TN:")?;
+        for (file, mut branches) in files {
+            branches.sort_by_key(|(line, ihr, right, _)| (*line, ihr.to_string(), *right));
+            let mut blocks: HashMap<Ihr, usize> = HashMap::new();
+            let mut block_taken: HashMap<Ihr, u64> = HashMap::new();
+            for (_, ihr, _, taken) in &branches {
+                let next = blocks.len();
+                blocks.entry(*ihr).or_insert(next);
+                *block_taken.entry(*ihr).or_default() += taken;
+            }
+
+            writeln!(output, "SF:{}", file.display())?;
+            let mut lines: BTreeMap<usize, u64> = BTreeMap::new();
+            for (line, ihr, right, taken) in &branches {
+                *lines.entry(*line).or_default() += taken;
+                // A dash marks a case node that never ran
+                let taken = match block_taken[ihr] {
+                    0 => "-".to_string(),
+                    _ => taken.to_string(),
+                };
+                writeln!(
+                    output,
+                    "BRDA:{},{},{},{}",
+                    line,
+                    blocks[ihr],
+                    u8::from(*right),
+                    taken
+                )?;
+            }
+            writeln!(output, "BRF:{}", branches.len())?;
+            let hit = branches.iter().filter(|branch| branch.3 > 0).count();
+            writeln!(output, "BRH:{}", hit)?;
+            for (line, count) in &lines {
+                writeln!(output, "DA:{},{}", line, count)?;
+            }
+            writeln!(output, "LF:{}", lines.len())?;
+            let hit = lines.values().filter(|count| **count > 0).count();
+            writeln!(output, "LH:{}", hit)?;
+            writeln!(output, "end_of_record")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use simfony::{Arguments, WitnessValues};
+    use simplicity::BitMachine;
+
+    use crate::tracker::Tracker;
+
+    #[test]
+    fn cover_branches_of_inlined_functions() {
+        // `check` is inlined at each call, and `jet::version()` occurs in both branches
+        let source = "\
+fn check(x: Either<u8, u8>) {
+    match x {
+        Left(a: u8) => {
+            let v: u32 = jet::version();
+            assert!(jet::eq_8(a, 1))
+        },
+        Right(b: u8) => {
+            let v: u32 = jet::version();
+            assert!(jet::eq_8(b, 2))
+        },
+    };
+}
+
+fn main() {
+    check(Left(1));
+    check(Left(1));
+    check(Right(2));
+}
+";
+        let program = Preprocessed::single_file(Path::new("src/main.simf"), source);
+        let compiled = CompiledProgram::new(source, Arguments::default(), true).unwrap();
+        let satisfied = compiled.satisfy(WitnessValues::default()).unwrap();
+        let node = satisfied.redeem();
+        let mut output = Vec::new();
+        let mut tracker = Tracker::new(satisfied.debug_symbols(), &program, &mut output);
+        let mut recorder = BranchRecorder::new(&mut tracker);
+        BitMachine::for_program(node)
+            .unwrap()
+            .exec_with_tracker(node, &simfony::dummy_env::dummy(), &mut recorder)
+            .unwrap();
+
+        let mut coverage = Coverage::default();
+        coverage.add(
+            &program,
+            &compiled,
+            node,
+            satisfied.debug_symbols(),
+            recorder.counts(),
+        );
+        let mut lcov = Vec::new();
+        coverage.write_lcov(&mut lcov).unwrap();
+        let lcov = String::from_utf8(lcov).unwrap();
+        let branches = lcov
+            .lines()
+            .filter(|line| line.starts_with("BRDA:"))
+            .collect::<Vec<_>>();
+        assert_eq!(branches, ["BRDA:5,0,0,2", "BRDA:9,0,1,1"]);
+    }
+
+    #[test]
+    fn report_branches_of_shared_calls() {
+        // The left branches of `check_a` and `check_b` make the same call
+        let source = "\
+fn check_a(x: Either<u8, u8>) {
+    match x {
+        Left(a: u8) => assert!(jet::eq_8(a, 1)),
+        Right(b: u8) => assert!(jet::eq_8(b, 2)),
+    };
+}
+
+fn check_b(x: Either<u8, u16>) {
+    match x {
+        Left(a: u8) => assert!(jet::eq_8(a, 1)),
+        Right(b: u16) => assert!(jet::eq_16(b, 3)),
+    };
+}
+
+fn main() {
+    check_a(Left(1));
+    check_b(Right(3));
+}
+";
+        let program = Preprocessed::single_file(Path::new("src/main.simf"), source);
+        let compiled = CompiledProgram::new(source, Arguments::default(), true).unwrap();
+        let satisfied = compiled.satisfy(WitnessValues::default()).unwrap();
+        let node = satisfied.redeem();
+        let mut output = Vec::new();
+        let mut tracker = Tracker::new(satisfied.debug_symbols(), &program, &mut output);
+        let mut recorder = BranchRecorder::new(&mut tracker);
+        BitMachine::for_program(node)
+            .unwrap()
+            .exec_with_tracker(node, &simfony::dummy_env::dummy(), &mut recorder)
+            .unwrap();
+
+        let mut coverage = Coverage::default();
+        coverage.add(
+            &program,
+            &compiled,
+            node,
+            satisfied.debug_symbols(),
+            recorder.counts(),
+        );
+        assert_eq!(coverage.unmapped(), 2);
+        let mut lcov = Vec::new();
+        coverage.write_lcov(&mut lcov).unwrap();
+        let lcov = String::from_utf8(lcov).unwrap();
+        // Both left branches are reported at the first occurrence of their call
+        let branches = lcov.lines().filter(|line| line.starts_with("BRDA:"));
+        assert_eq!(branches.count(), 4);
+        let lines = lcov
+            .lines()
+            .filter(|line| line.starts_with("DA:"))
+            .collect::<Vec<_>>();
+        assert_eq!(lines, ["DA:3,1", "DA:4,0", "DA:11,1"]);
+    }
+}
@@ -0,0 +1,473 @@
+//! Interactive debugger that pauses the Bit Machine on breakpoints.
+
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+use itertools::Either;
+
+use simfony::debug::DebugSymbols;
+use simfony::value::StructuralValue;
+
+use simplicity::bit_machine::ExecTracker;
+use simplicity::ffi::ffi::UWORD;
+use simplicity::jet::Elements;
+use simplicity::Cmr;
+
+use simfony_cli::source_map::Preprocessed;
+
+use crate::tracker::{parse_args, parse_result, CallLocator, CallSite, CallStack};
+
+const HELP: &str = "\
+Commands:
+  s, step             Run to the next jet call or dbg! site
+  c, continue         Run to the next breakpoint
+  p, print            Print the values at the current point
+  w, where            Print the current location
+  b, break <BP>       Add a breakpoint: `fn <name>`, `jet <name>` or `<file>:<line>`
+                      (`fn <name>` fires when a call of the function reaches its first
+                      jet call or dbg! site, so never for functions without them)
+  d, delete <N>       Delete a breakpoint
+  i, info             List the breakpoints
+  q, quit             Stop debugging and run to completion
+  h, help             Print this help";
+
+/// Condition under which the debugger pauses the execution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Execution enters a function, detected at its first jet call or `dbg!` site
+    Function(String),
+    /// Execution reaches a jet call
+    Jet(String),
+    /// Execution reaches a `dbg!` site at a `file:line` location
+    Site(String),
+}
+
+impl FromStr for Breakpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(name) = s.strip_prefix("fn ") {
+            Ok(Self::Function(name.trim().to_string()))
+        } else if let Some(name) = s.strip_prefix("jet ") {
+            let name = name.trim();
+            Ok(Self::Jet(
+                name.strip_prefix("jet::").unwrap_or(name).to_string(),
+            ))
+        } else if s
+            .rsplit_once(':')
+            .is_some_and(|(_, line)| line.parse::<usize>().is_ok())
+        {
+            Ok(Self::Site(s.to_string()))
+        } else {
+            Err(format!(
+                "Invalid breakpoint `{}`: expected `fn <name>`, `jet <name>` or `<file>:<line>`",
+                s
+            ))
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Function(name) => write!(f, "fn {}", name),
+            Self::Jet(name) => write!(f, "jet {}", name),
+            Self::Site(location) => write!(f, "{}", location),
+        }
+    }
+}
+
+/// Point of the execution at which the debugger paused.
+enum Event {
+    Enter {
+        function: String,
+    },
+    Jet {
+        name: String,
+        args: Vec<String>,
+        result: String,
+    },
+    Debug {
+        text: String,
+        value: String,
+    },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Enter { function } => write!(f, "entered fn {}", function),
+            Self::Jet { name, args, result } => {
+                write!(f, "jet {}({}) = {}", name, args.join(", "), result)
+            }
+            Self::Debug { text, value } => write!(f, "dbg!({}) = {}", text, value),
+        }
+    }
+}
+
+/// Execution tracker that pauses on breakpoints and reads commands from the input.
+pub struct Debugger<'a, R: BufRead, W: Write> {
+    debug_symbols: &'a DebugSymbols,
+    locator: CallLocator<'a>,
+    stack: CallStack,
+    input: R,
+    output: &'a mut W,
+    breakpoints: Vec<Breakpoint>,
+    /// Pause on the next jet call or `dbg!` site
+    stepping: bool,
+    /// Run to completion without pausing
+    detached: bool,
+    /// Preprocessed line of the last tracked call
+    current_line: Option<usize>,
+    current_function: Option<String>,
+}
+
+impl<'a, R: BufRead, W: Write> Debugger<'a, R, W> {
+    /// Creates a debugger. Without breakpoints it pauses on the first jet call or `dbg!` site.
+    pub fn new(
+        debug_symbols: &'a DebugSymbols,
+        program: &'a Preprocessed,
+        breakpoints: Vec<Breakpoint>,
+        input: R,
+        output: &'a mut W,
+    ) -> Self {
+        Self {
+            debug_symbols,
+            locator: CallLocator::new(program),
+            stack: CallStack::default(),
+            input,
+            output,
+            stepping: breakpoints.is_empty(),
+            breakpoints,
+            detached: false,
+            current_line: None,
+            current_function: None,
+        }
+    }
+
+    /// Returns the original location and the function of the current line.
+    fn location(&self) -> String {
+        let location = self.current_line.map(|line| {
+            self.locator
+                .location(line)
+                .map_or_else(|| format!("line {}", line), ToString::to_string)
+        });
+        match (location, &self.current_function) {
+            (Some(location), Some(function)) => format!("{} in fn {}", location, function),
+            (Some(location), None) => location,
+            (None, Some(function)) => format!("fn {}", function),
+            (None, None) => "unknown location".to_string(),
+        }
+    }
+
+    /// Pauses the execution and handles commands until it is resumed.
+    fn pause(&mut self, reason: Option<&Breakpoint>, event: &Event) {
+        let reason = reason
+            .map(|breakpoint| format!(" at breakpoint `{}`", breakpoint))
+            .unwrap_or_default();
+        let _ = writeln!(
+            self.output,
+            "\x1b[1;36mStopped{}: {}\x1b[0m\n  at {}",
+            reason,
+            event,
+            self.location()
+        );
+
+        loop {
+            let _ = write!(self.output, "(simfony) ");
+            let _ = self.output.flush();
+            let mut line = String::new();
+            if !self.input.read_line(&mut line).is_ok_and(|n| n > 0) {
+                self.detached = true;
+                return;
+            }
+            let (command, argument) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+            match command {
+                "s" | "step" => {
+                    self.stepping = true;
+                    return;
+                }
+                "c" | "continue" => {
+                    self.stepping = false;
+                    return;
+                }
+                "q" | "quit" => {
+                    self.detached = true;
+                    return;
+                }
+                "p" | "print" => self.print(event),
+                "w" | "where" => {
+                    let location = self.location();
+                    let _ = writeln!(self.output, "{}", location);
+                }
+                "b" | "break" => match argument.parse::<Breakpoint>() {
+                    Ok(breakpoint) => {
+                        let _ = writeln!(
+                            self.output,
+                            "Breakpoint {}: {}",
+                            self.breakpoints.len() + 1,
+                            breakpoint
+                        );
+                        self.breakpoints.push(breakpoint);
+                    }
+                    Err(error) => {
+                        let _ = writeln!(self.output, "{}", error);
+                    }
+                },
+                "d" | "delete" => match argument.trim().parse::<usize>() {
+                    Ok(n) if 0 < n && n <= self.breakpoints.len() => {
+                        let breakpoint = self.breakpoints.remove(n - 1);
+                        let _ = writeln!(self.output, "Deleted breakpoint {}: {}", n, breakpoint);
+                    }
+                    _ => {
+                        let _ = writeln!(self.output, "No breakpoint `{}`", argument.trim());
+                    }
+                },
+                "i" | "info" => {
+                    if self.breakpoints.is_empty() {
+                        let _ = writeln!(self.output, "No breakpoints");
+                    }
+                    for (i, breakpoint) in self.breakpoints.iter().enumerate() {
+                        let _ = writeln!(self.output, "{}: {}", i + 1, breakpoint);
+                    }
+                }
+                "h" | "help" => {
+                    let _ = writeln!(self.output, "{}", HELP);
+                }
+                "" => {}
+                _ => {
+                    let _ = writeln!(self.output, "Unknown command `{}`, try `help`", command);
+                }
+            }
+        }
+    }
+
+    /// Prints the values at the current point, one per line.
+    fn print(&mut self, event: &Event) {
+        let _ = match event {
+            Event::Enter { function } => writeln!(self.output, "fn {}", function),
+            Event::Jet { name, args, result } => {
+                for (i, arg) in args.iter().enumerate() {
+                    let _ = writeln!(self.output, "{} arg {} = {}", name, i, arg);
+                }
+                writeln!(self.output, "{} result = {}", name, result)
+            }
+            Event::Debug { text, value } => writeln!(self.output, "{} = {}", text, value),
+        };
+    }
+
+    /// Updates the current line and function, returning the function if it was entered.
+    /// A call whose text occurs in several functions leaves both unknown.
+    fn enter(&mut self, cmr: &Cmr, text: &str) -> Option<String> {
+        match self.locator.site(cmr, text, &self.stack) {
+            CallSite::Function { name, line } => {
+                self.current_line = line;
+                self.current_function = Some(name.clone());
+                self.stack.track(&name, cmr).then_some(name)
+            }
+            CallSite::Ambiguous | CallSite::Unknown => {
+                self.current_line = None;
+                self.current_function = None;
+                None
+            }
+        }
+    }
+
+    /// Updates the current line and function with a jet call, leaving the call stack as it is.
+    /// A jet call whose function is not known leaves both unknown, rather than showing
+    /// the location of the last tracked call.
+    fn locate_jet(&mut self, name: &str) {
+        match self.locator.jet_site(name, &self.stack) {
+            CallSite::Function { name, line } => {
+                self.current_line = line;
+                self.current_function = Some(name);
+            }
+            CallSite::Ambiguous | CallSite::Unknown => {
+                self.current_line = None;
+                self.current_function = None;
+            }
+        }
+    }
+
+    /// Updates the current point with a tracked call and pauses if it enters
+    /// a function with a breakpoint.
+    fn reach(&mut self, cmr: &Cmr, text: &str) {
+        if let Some(function) = self.enter(cmr, text) {
+            let breakpoint = self
+                .breakpoints
+                .iter()
+                .find(|breakpoint| matches!(breakpoint, Breakpoint::Function(n) if *n == function))
+                .cloned();
+            if breakpoint.is_some() {
+                self.pause(breakpoint.as_ref(), &Event::Enter { function });
+            }
+        }
+    }
+}
+
+impl<'a, R: BufRead, W: Write> ExecTracker<Elements> for Debugger<'a, R, W> {
+    fn track_left(&mut self, _: simplicity::Ihr) {}
+
+    fn track_right(&mut self, _: simplicity::Ihr) {}
+
+    fn track_jet_call(
+        &mut self,
+        jet: &Elements,
+        input_buffer: &[UWORD],
+        output_buffer: &[UWORD],
+        _: bool,
+    ) {
+        if self.detached {
+            return;
+        }
+        let name = jet.to_string();
+        let breakpoint = self
+            .breakpoints
+            .iter()
+            .find(|breakpoint| matches!(breakpoint, Breakpoint::Jet(n) if *n == name))
+            .cloned();
+        if !self.stepping && breakpoint.is_none() {
+            return;
+        }
+
+        let args = match parse_args(jet, input_buffer) {
+            Ok(args) => args.iter().map(ToString::to_string).collect(),
+            Err(error) => vec![format!("<TrackerError: {}>", error)],
+        };
+        let result = parse_result(jet, output_buffer).map_or_else(
+            |error| format!("<TrackerError: {}>", error),
+            |value| value.to_string(),
+        );
+        self.locate_jet(&name);
+        self.pause(breakpoint.as_ref(), &Event::Jet { name, args, result });
+    }
+
+    fn track_dbg_call(&mut self, cmr: &Cmr, value: simplicity::Value) {
+        if self.detached {
+            return;
+        }
+        let debug_symbols = self.debug_symbols;
+        let Some(tracked_call) = debug_symbols.get(cmr) else {
+            return;
+        };
+
+        self.reach(cmr, tracked_call.text());
+
+        if let Some(Either::Right(debug_value)) =
+            tracked_call.map_value(&StructuralValue::from(value))
+        {
+            let location = self
+                .current_line
+                .and_then(|line| self.locator.location(line))
+                .map(ToString::to_string);
+            let breakpoint = self
+                .breakpoints
+                .iter()
+                .find(|breakpoint| match (breakpoint, &location) {
+                    (Breakpoint::Site(site), Some(location)) => {
+                        location == site || location.ends_with(&format!("/{}", site))
+                    }
+                    _ => false,
+                })
+                .cloned();
+            if self.stepping || breakpoint.is_some() {
+                let event = Event::Debug {
+                    text: debug_value.text().to_string(),
+                    value: debug_value.value().to_string(),
+                };
+                self.pause(breakpoint.as_ref(), &event);
+            }
+        }
+    }
+}
+
+/// Parses the breakpoints given on the command line.
+pub fn parse_breakpoints(specs: &[String]) -> anyhow::Result<Vec<Breakpoint>> {
+    specs
+        .iter()
+        .map(|spec| spec.parse().map_err(|e: String| anyhow::anyhow!(e)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn parse_breakpoint() {
+        assert_eq!(
+            "fn fold_line".parse(),
+            Ok(Breakpoint::Function("fold_line".to_string()))
+        );
+        assert_eq!(
+            "jet jet::add_32".parse(),
+            Ok(Breakpoint::Jet("add_32".to_string()))
+        );
+        assert_eq!(
+            "src/fri.simf:42".parse(),
+            Ok(Breakpoint::Site("src/fri.simf:42".to_string()))
+        );
+        assert!("fold_line".parse::<Breakpoint>().is_err());
+    }
+
+    #[test]
+    fn break_on_repeated_calls() {
+        let source = "fn square(x: u32) -> u64 {\n    jet::multiply_32(x, x)\n}\n\
+                      fn main() {\n    let a: u64 = square(2);\n    let b: u64 = square(3);\n}\n";
+        let program = Preprocessed::single_file(Path::new("main.simf"), source);
+        let debug_symbols = DebugSymbols::default();
+        let mut output = Vec::new();
+        let mut debugger = Debugger::new(
+            &debug_symbols,
+            &program,
+            vec![Breakpoint::Function("square".to_string())],
+            "c\nc\n".as_bytes(),
+            &mut output,
+        );
+
+        // Two calls in a row, without any other tracked call in between
+        let multiply = Cmr::from_byte_array([1; 32]);
+        debugger.reach(&multiply, "jet::multiply_32(x, x)");
+        debugger.reach(&multiply, "jet::multiply_32(x, x)");
+        assert!(!debugger.detached);
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output.matches("Stopped at breakpoint `fn square`").count(),
+            2
+        );
+        assert!(output.contains("at main.simf:2 in fn square"));
+    }
+
+    #[test]
+    fn locate_jet_breakpoints() {
+        let source = "fn square(x: u32) -> u64 {\n    jet::multiply_32(x, x)\n}\n\
+                      fn main() {\n    let a: u64 = square(2);\n    \
+                      let (_, b): (bool, u32) = jet::add_32(1, 2);\n}\n";
+        let program = Preprocessed::single_file(Path::new("main.simf"), source);
+        let debug_symbols = DebugSymbols::default();
+        let mut output = Vec::new();
+        let mut debugger = Debugger::new(
+            &debug_symbols,
+            &program,
+            vec![
+                Breakpoint::Jet("multiply_32".to_string()),
+                Breakpoint::Jet("add_32".to_string()),
+            ],
+            "c\nc\n".as_bytes(),
+            &mut output,
+        );
+
+        // The jet runs before its tracked call, which is the last one reached in `square`
+        debugger.track_jet_call(&Elements::Multiply32, &[], &[], true);
+        debugger.reach(&Cmr::from_byte_array([1; 32]), "jet::multiply_32(x, x)");
+        debugger.track_jet_call(&Elements::Add32, &[], &[], true);
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("at main.simf:2 in fn square"));
+        assert!(output.contains("at main.simf:6 in fn main"));
+    }
+}
@@ -0,0 +1,156 @@
+//! Inspection of encoded Simplicity programs.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use simplicity::dag::{DagLike, InternalSharing};
+use simplicity::elements::encode::deserialize;
+use simplicity::elements::Transaction;
+use simplicity::jet::Elements;
+use simplicity::node::{CommitNode, Inner, Marker, Node};
+use simplicity::{BitIter, RedeemNode};
+
+/// Text encoding of bytes given inline or in a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Hex,
+    Base64,
+}
+
+/// Reads bytes given inline or in a file, as hex, base64 or raw bytes.
+/// The `Program:` header of `simfony build` output is skipped.
+///
+/// Without an `encoding`, text that is both valid hex and valid base64 (e.g. `deadbeef`)
+/// is rejected instead of guessing; text that is only valid in one of them is decoded.
+pub fn read_bytes(input: &str, encoding: Option<Encoding>) -> Result<Vec<u8>> {
+    let path = Path::new(input);
+    let bytes = if path.is_file() {
+        fs::read(path).with_context(|| format!("Failed to read file: {}", path.display()))?
+    } else {
+        input.as_bytes().to_vec()
+    };
+
+    let Ok(text) = std::str::from_utf8(&bytes) else {
+        return Ok(bytes);
+    };
+    let text = text.trim();
+    let text = text.strip_prefix("Program:").unwrap_or(text).trim();
+    match encoding {
+        Some(Encoding::Hex) => {
+            return hex::decode(text).map_err(|e| anyhow!("Input is not valid hex: {}", e))
+        }
+        Some(Encoding::Base64) => {
+            return STANDARD
+                .decode(text)
+                .map_err(|e| anyhow!("Input is not valid base64: {}", e))
+        }
+        None => {}
+    }
+    match (hex::decode(text), STANDARD.decode(text)) {
+        (Ok(_), Ok(_)) => Err(anyhow!(
+            "Input is valid as both hex and base64, pass --hex or --base64"
+        )),
+        (Ok(decoded), Err(_)) | (Err(_), Ok(decoded)) => Ok(decoded),
+        (Err(_), Err(_)) if path.is_file() => Ok(bytes),
+        (Err(_), Err(_)) => Err(anyhow!("Input is neither a file, hex nor base64")),
+    }
+}
+
+/// Returns the encoded witness and program from the script witness of a transaction input
+/// that spends a Simplicity program.
+pub fn spending_stack(tx_bytes: &[u8], input_index: u32) -> Result<(Vec<u8>, Vec<u8>)> {
+    let tx: Transaction =
+        deserialize(tx_bytes).map_err(|e| anyhow!("Invalid transaction: {}", e))?;
+    let input = tx
+        .input
+        .get(input_index as usize)
+        .ok_or_else(|| anyhow!("Transaction has no input {}", input_index))?;
+    // The stack is witness, program, CMR script, control block and an optional annex
+    match input.witness.script_witness.as_slice() {
+        [witness, program, _, _, ..] => Ok((witness.clone(), program.clone())),
+        _ => Err(anyhow!(
+            "Input {} does not spend a Simplicity program: its script witness is too short",
+            input_index
+        )),
+    }
+}
+
+/// Decodes a program, and the witness if given, and prints its roots, types, bounds and jets.
+pub fn print_program(program_bytes: &[u8], witness_bytes: Option<&[u8]>) -> Result<()> {
+    match witness_bytes {
+        Some(witness_bytes) => {
+            let node = RedeemNode::<Elements>::decode(
+                BitIter::from(program_bytes.iter().copied()),
+                BitIter::from(witness_bytes.iter().copied()),
+            )
+            .map_err(|e| anyhow!("Failed to decode program: {}", e))?;
+            println!("CMR: {}", node.cmr());
+            println!("IMR: {}", node.ihr());
+            println!("AMR: {}", node.amr());
+            println!("Type: {} -> {}", node.arrow().source, node.arrow().target);
+            println!("Node bounds: {:?}", node.bounds());
+            println!("Program size: {}", program_bytes.len());
+            println!("Witness size: {}", witness_bytes.len());
+            print_jets(&node);
+        }
+        None => {
+            let node = CommitNode::<Elements>::decode(BitIter::from(program_bytes.iter().copied()))
+                .map_err(|e| anyhow!("Failed to decode program: {}", e))?;
+            println!("CMR: {}", node.cmr());
+            println!("IMR: unknown without witness");
+            println!("AMR: unknown without witness");
+            println!("Type: {} -> {}", node.arrow().source, node.arrow().target);
+            println!("Program size: {}", program_bytes.len());
+            print_jets(&node);
+        }
+    }
+    Ok(())
+}
+
+/// Prints how many distinct nodes of each jet the program contains.
+fn print_jets<N: Marker<Jet = Elements>>(node: &Node<N>) {
+    let mut jets = BTreeMap::new();
+    for item in node.post_order_iter::<InternalSharing>() {
+        if let Inner::Jet(jet) = item.node.inner() {
+            *jets.entry(jet.to_string()).or_insert(0usize) += 1;
+        }
+    }
+
+    println!(
+        "Jets: {} distinct, {} nodes",
+        jets.len(),
+        jets.values().sum::<usize>()
+    );
+    for (jet, count) in jets {
+        println!("  {:<40} {:>6}", jet, count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_inline_bytes() {
+        let bytes = vec![0xde, 0xad, 0xbe, 0xef];
+        assert_eq!(read_bytes("deadbeef", Some(Encoding::Hex)).unwrap(), bytes);
+        assert_eq!(read_bytes("Program:\n3q2+7w==", None).unwrap(), bytes);
+        assert!(read_bytes("not a program", None).is_err());
+
+        // Hex digits only, but a valid base64 program as well
+        assert!(read_bytes("deadbeef", None).is_err());
+        assert_eq!(
+            read_bytes("deadbeef", Some(Encoding::Base64)).unwrap(),
+            vec![0x75, 0xe6, 0x9d, 0x6d, 0xe7, 0x9f]
+        );
+        // Not a multiple of 4 characters, so only hex
+        assert_eq!(
+            read_bytes("deadbeef00", None).unwrap(),
+            vec![0xde, 0xad, 0xbe, 0xef, 0x00]
+        );
+    }
+}
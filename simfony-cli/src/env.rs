@@ -0,0 +1,315 @@
+//! Transaction environments for executing programs.
+
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use simplicity::elements::encode::deserialize;
+use simplicity::elements::hashes::Hash;
+use simplicity::elements::taproot::ControlBlock;
+use simplicity::elements::{
+    confidential, AssetId, AssetIssuance, BlockHash, LockTime, OutPoint, Script, Sequence,
+    Transaction, TxIn, TxInWitness, TxOut, TxOutWitness, Txid,
+};
+use simplicity::jet::elements::{ElementsEnv, ElementsUtxo};
+use simplicity::Cmr;
+
+use simfony_cli::taproot;
+
+/// Genesis block hash of the Liquid testnet
+pub const LIQUID_TESTNET_GENESIS: &str =
+    "a771da8e52ee6ad581ed1e9a99825e5b3b7992225534eaa2ae23244fe26ab1c1";
+
+/// A transaction output, such as an output spent by the transaction.
+#[derive(Deserialize)]
+pub struct Output {
+    /// Script pubkey in hex
+    #[serde(default)]
+    pub script_pubkey: String,
+    /// Explicit asset id, or asset commitment, in hex
+    pub asset: String,
+    /// Explicit amount in satoshis, or value commitment in hex
+    pub value: OutputValue,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum OutputValue {
+    Explicit(u64),
+    Confidential(String),
+}
+
+impl Output {
+    pub fn to_utxo(&self) -> Result<ElementsUtxo> {
+        let output = self.to_tx_out()?;
+        Ok(ElementsUtxo {
+            script_pubkey: output.script_pubkey,
+            asset: output.asset,
+            value: output.value,
+        })
+    }
+
+    pub fn to_tx_out(&self) -> Result<TxOut> {
+        let script_pubkey = hex::decode(&self.script_pubkey)
+            .with_context(|| format!("Invalid script pubkey: {}", self.script_pubkey))?;
+        Ok(TxOut {
+            script_pubkey: Script::from(script_pubkey),
+            asset: parse_asset(&self.asset)?,
+            value: match &self.value {
+                OutputValue::Explicit(amount) => confidential::Value::Explicit(*amount),
+                OutputValue::Confidential(commitment) => {
+                    let bytes = hex::decode(commitment)
+                        .with_context(|| format!("Invalid value commitment: {}", commitment))?;
+                    confidential::Value::from_commitment(&bytes)
+                        .map_err(|e| anyhow!("Invalid value commitment {}: {}", commitment, e))?
+                }
+            },
+            nonce: confidential::Nonce::Null,
+            witness: TxOutWitness::default(),
+        })
+    }
+}
+
+/// Synthetic spending context for testing programs without a real transaction.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvSpec {
+    #[serde(default = "default_version")]
+    pub version: u32,
+    #[serde(default)]
+    pub lock_time: u32,
+    /// Index of the input that spends the program
+    #[serde(default)]
+    pub input_index: u32,
+    pub inputs: Vec<InputSpec>,
+    #[serde(default)]
+    pub outputs: Vec<Output>,
+    /// Taproot control block in hex (default: created from the internal key)
+    pub control_block: Option<String>,
+    /// X-only internal key in hex (default: unspendable key)
+    pub internal_key: Option<String>,
+    /// Annex in hex
+    pub annex: Option<String>,
+    /// Genesis block hash (default: Liquid testnet)
+    pub genesis_hash: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InputSpec {
+    /// Spent outpoint as `txid:vout` (default: zero txid and the input index)
+    pub outpoint: Option<String>,
+    #[serde(default = "default_sequence")]
+    pub sequence: u32,
+    /// Spent output
+    pub utxo: Output,
+}
+
+fn default_version() -> u32 {
+    2
+}
+
+fn default_sequence() -> u32 {
+    Sequence::MAX.0
+}
+
+impl EnvSpec {
+    /// Reads a spec from a TOML file, or a JSON file for any other extension.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read environment spec: {}", path.display()))?;
+        if path.extension().is_some_and(|ext| ext == "toml") {
+            toml::from_str(&content)
+                .with_context(|| format!("Failed to parse environment spec: {}", path.display()))
+        } else {
+            serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse environment spec: {}", path.display()))
+        }
+    }
+
+    /// Builds the environment in which the program with the given CMR spends the input.
+    pub fn to_env(&self, script_cmr: Cmr) -> Result<ElementsEnv<Arc<Transaction>>> {
+        let input = self
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(index, input)| {
+                let previous_output = match &input.outpoint {
+                    Some(outpoint) => OutPoint::from_str(outpoint)
+                        .map_err(|e| anyhow!("Invalid outpoint {}: {}", outpoint, e))?,
+                    None => OutPoint::new(Txid::all_zeros(), index as u32),
+                };
+                Ok(TxIn {
+                    previous_output,
+                    is_pegin: false,
+                    script_sig: Script::new(),
+                    sequence: Sequence(input.sequence),
+                    asset_issuance: AssetIssuance::null(),
+                    witness: TxInWitness::empty(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let output = self
+            .outputs
+            .iter()
+            .map(Output::to_tx_out)
+            .collect::<Result<Vec<_>>>()?;
+        let tx = Transaction {
+            version: self.version,
+            lock_time: LockTime::from_consensus(self.lock_time),
+            input,
+            output,
+        };
+        let utxos: Vec<&Output> = self.inputs.iter().map(|input| &input.utxo).collect();
+
+        let control_block = match &self.control_block {
+            Some(control_block) => control_block.clone(),
+            None => {
+                let internal_key = taproot::parse_internal_key(
+                    self.internal_key
+                        .as_deref()
+                        .unwrap_or(taproot::UNSPENDABLE_INTERNAL_KEY),
+                )?;
+                hex::encode(taproot::control_block(script_cmr, internal_key)?.serialize())
+            }
+        };
+
+        build_env(
+            tx,
+            &utxos,
+            self.input_index,
+            script_cmr,
+            &control_block,
+            self.annex.as_deref(),
+            self.genesis_hash.as_deref(),
+        )
+    }
+}
+
+/// Parses an explicit asset id (32 bytes) or an asset commitment (33 bytes) in hex.
+pub fn parse_asset(asset: &str) -> Result<confidential::Asset> {
+    let bytes = hex::decode(asset).with_context(|| format!("Invalid asset: {}", asset))?;
+    match bytes.len() {
+        32 => Ok(confidential::Asset::Explicit(
+            AssetId::from_str(asset).map_err(|e| anyhow!("Invalid asset id {}: {}", asset, e))?,
+        )),
+        _ => confidential::Asset::from_commitment(&bytes)
+            .map_err(|e| anyhow!("Invalid asset commitment {}: {}", asset, e)),
+    }
+}
+
+/// Builds the environment in which a node validates an input of the transaction
+/// that spends a program through the taproot script path.
+pub fn transaction_env(
+    tx_bytes: &[u8],
+    utxos: &[Output],
+    input_index: u32,
+    script_cmr: Cmr,
+    control_block: &str,
+    annex: Option<&str>,
+    genesis_hash: Option<&str>,
+) -> Result<ElementsEnv<Arc<Transaction>>> {
+    let tx: Transaction =
+        deserialize(tx_bytes).map_err(|e| anyhow!("Invalid transaction: {}", e))?;
+    let utxos: Vec<&Output> = utxos.iter().collect();
+    build_env(
+        tx,
+        &utxos,
+        input_index,
+        script_cmr,
+        control_block,
+        annex,
+        genesis_hash,
+    )
+}
+
+fn build_env(
+    tx: Transaction,
+    utxos: &[&Output],
+    input_index: u32,
+    script_cmr: Cmr,
+    control_block: &str,
+    annex: Option<&str>,
+    genesis_hash: Option<&str>,
+) -> Result<ElementsEnv<Arc<Transaction>>> {
+    if input_index as usize >= tx.input.len() {
+        return Err(anyhow!(
+            "Input index {} is out of range, the transaction has {} inputs",
+            input_index,
+            tx.input.len()
+        ));
+    }
+    if utxos.len() != tx.input.len() {
+        return Err(anyhow!(
+            "Expected {} UTXOs, one per transaction input, got {}",
+            tx.input.len(),
+            utxos.len()
+        ));
+    }
+    let utxos = utxos
+        .iter()
+        .map(|utxo| utxo.to_utxo())
+        .collect::<Result<Vec<_>>>()?;
+
+    let control_block = hex::decode(control_block)
+        .ok()
+        .and_then(|bytes| ControlBlock::from_slice(&bytes).ok())
+        .ok_or(anyhow!("Invalid control block: {}", control_block))?;
+    let annex = annex
+        .map(|annex| hex::decode(annex).with_context(|| format!("Invalid annex: {}", annex)))
+        .transpose()?;
+    let genesis_hash = genesis_hash.unwrap_or(LIQUID_TESTNET_GENESIS);
+    let genesis_hash = BlockHash::from_str(genesis_hash)
+        .map_err(|e| anyhow!("Invalid genesis hash {}: {}", genesis_hash, e))?;
+
+    Ok(ElementsEnv::new(
+        Arc::new(tx),
+        utxos,
+        input_index,
+        script_cmr,
+        control_block,
+        annex,
+        genesis_hash,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_toml_spec() {
+        let spec: EnvSpec = toml::from_str(
+            r#"
+            lock_time = 500000
+            input_index = 1
+
+            [[inputs]]
+            utxo = { asset = "144c654344aa716d6f3abcc1ca90e5641e4e2a7f633bc09fe3baf64585819a49", value = 1000 }
+
+            [[inputs]]
+            sequence = 0
+            utxo = { script_pubkey = "5120", asset = "144c654344aa716d6f3abcc1ca90e5641e4e2a7f633bc09fe3baf64585819a49", value = 2000 }
+
+            [[outputs]]
+            script_pubkey = "0014"
+            asset = "144c654344aa716d6f3abcc1ca90e5641e4e2a7f633bc09fe3baf64585819a49"
+            value = 2900
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(spec.version, 2);
+        assert_eq!(spec.input_index, 1);
+        assert_eq!(spec.inputs[0].sequence, u32::MAX);
+        assert_eq!(spec.inputs[1].sequence, 0);
+        assert!(matches!(spec.outputs[0].value, OutputValue::Explicit(2900)));
+
+        let output = spec.outputs[0].to_tx_out().unwrap();
+        assert_eq!(output.value, confidential::Value::Explicit(2900));
+        assert_eq!(output.script_pubkey.as_bytes(), [0x00, 0x14]);
+    }
+}
@@ -0,0 +1,308 @@
+//! Source formatter for SimplicityHL.
+//!
+//! The formatter works on tokens rather than on the syntax tree, so comments, preprocessor
+//! directives and macro invocations are preserved. Line breaks are kept as written:
+//! every line is indented by the nesting of its brackets, one level more if it continues
+//! a statement or a match arm of the previous line, and its tokens are re-spaced.
+//! Directives are printed unchanged at the start of the line.
+
+use anyhow::{anyhow, Result};
+
+const INDENT: &str = "    ";
+
+/// Keywords that are followed by a space before an opening bracket.
+const KEYWORDS: [&str; 6] = ["let", "match", "type", "mod", "const", "fn"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Word,
+    Punct,
+    Comment,
+    /// Preprocessor directive with its continuation lines
+    Directive,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Token<'a> {
+    kind: Kind,
+    text: &'a str,
+}
+
+impl Token<'_> {
+    fn is_opener(&self) -> bool {
+        self.kind == Kind::Punct && matches!(self.text, "(" | "[" | "{")
+    }
+
+    fn is_closer(&self) -> bool {
+        self.kind == Kind::Punct && matches!(self.text, ")" | "]" | "}")
+    }
+}
+
+/// Formats a source file.
+///
+/// Returns an error if formatting would change anything but whitespace,
+/// which would be a bug of the formatter.
+pub fn format_source(source: &str) -> Result<String> {
+    let formatted = format_lines(&lex(source));
+    let tokens = |source: &str| -> Vec<Vec<String>> {
+        lex(source)
+            .into_iter()
+            .flatten()
+            .map(|token| {
+                token
+                    .text
+                    .lines()
+                    .map(|line| line.trim_end().to_string())
+                    .collect()
+            })
+            .collect()
+    };
+    if tokens(source) != tokens(&formatted) {
+        return Err(anyhow!("Formatting would change the tokens of the source"));
+    }
+    Ok(formatted)
+}
+
+/// Splits the source code into lines of tokens.
+/// Multi-line comments and directives are single tokens of the line where they start.
+fn lex(source: &str) -> Vec<Vec<Token<'_>>> {
+    let mut lines = vec![Vec::new()];
+    let mut index = 0;
+    while let Some(c) = source[index..].chars().next() {
+        let rest = &source[index..];
+        if c == '\n' {
+            lines.push(Vec::new());
+            index += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            index += c.len_utf8();
+            continue;
+        }
+
+        let at_line_start = lines.last().is_some_and(Vec::is_empty);
+        let (kind, len) = if c == '#' && at_line_start {
+            let mut len = 0;
+            for line in rest.split_inclusive('\n') {
+                len += line.len();
+                if !line.trim_end().ends_with('\\') {
+                    break;
+                }
+            }
+            (
+                Kind::Directive,
+                len - usize::from(rest[..len].ends_with('\n')),
+            )
+        } else if rest.starts_with("//") {
+            (Kind::Comment, rest.find('\n').unwrap_or(rest.len()))
+        } else if rest.starts_with("/*") {
+            (
+                Kind::Comment,
+                rest.find("*/").map_or(rest.len(), |end| end + 2),
+            )
+        } else if c == '"' {
+            let end = rest[1..].find('"').map_or(rest.len(), |end| end + 2);
+            (Kind::Word, end)
+        } else if c.is_alphanumeric() || c == '_' {
+            let end = rest
+                .find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            (Kind::Word, end)
+        } else if ["::", "->", "=>"].iter().any(|op| rest.starts_with(op)) {
+            (Kind::Punct, 2)
+        } else {
+            (Kind::Punct, c.len_utf8())
+        };
+
+        let text = &rest[..len];
+        lines
+            .last_mut()
+            .expect("at least one line")
+            .push(Token { kind, text });
+        // Multi-line tokens are continued by the rest of their last line
+        index += len;
+    }
+    lines
+}
+
+/// Prints the lines of tokens with canonical indentation and spacing.
+fn format_lines(lines: &[Vec<Token>]) -> String {
+    let mut output = String::new();
+    // Indentation of the lines that opened the unclosed brackets
+    let mut brackets: Vec<usize> = Vec::new();
+    // Whether the statement inside each unclosed bracket is not terminated yet
+    let mut continued: Vec<bool> = Vec::new();
+    // Bracket nesting at the start of each enclosing conditional block
+    let mut conditionals: Vec<(Vec<usize>, Vec<bool>)> = Vec::new();
+    let mut blank_line = false;
+
+    for line in lines {
+        let Some(first) = line.first() else {
+            blank_line = true;
+            continue;
+        };
+        if blank_line && !output.is_empty() {
+            output.push('\n');
+        }
+        blank_line = false;
+
+        if first.kind == Kind::Directive {
+            // The branches of a conditional block start with the same nesting
+            let directive = first.text.trim_start_matches('#').trim_start();
+            if directive.starts_with("if") {
+                conditionals.push((brackets.clone(), continued.clone()));
+            } else if directive.starts_with("el") {
+                if let Some(saved) = conditionals.last() {
+                    (brackets, continued) = saved.clone();
+                }
+            } else if directive.starts_with("endif") {
+                conditionals.pop();
+            }
+            push_directive(&mut output, first.text);
+            continue;
+        }
+
+        let indent = match brackets.last() {
+            Some(indent) if first.is_closer() => *indent,
+            Some(indent) => indent + 1 + usize::from(continued.last() == Some(&true)),
+            None => 0,
+        };
+        output.push_str(&INDENT.repeat(indent));
+        for (index, token) in line.iter().enumerate() {
+            if index > 0 && needs_space(&line[index - 1], token) {
+                output.push(' ');
+            }
+            match token.kind {
+                Kind::Comment => output.push_str(token.text.trim_end()),
+                _ => output.push_str(token.text),
+            }
+            if token.is_opener() {
+                brackets.push(indent);
+                continued.push(false);
+            } else if token.is_closer() {
+                brackets.pop();
+                continued.pop();
+                // A block ends the statement, a call or a tuple continues it
+                if let Some(continued) = continued.last_mut() {
+                    *continued = token.text != "}";
+                }
+            } else if token.kind != Kind::Comment {
+                if let Some(continued) = continued.last_mut() {
+                    *continued = !matches!(token.text, ";" | ",");
+                }
+            }
+        }
+        output.push('\n');
+    }
+    output
+}
+
+/// Prints a directive, removing the trailing whitespace of its lines.
+fn push_directive(output: &mut String, directive: &str) {
+    for (index, line) in directive.lines().enumerate() {
+        if index > 0 {
+            output.push('\n');
+        }
+        output.push_str(line.trim_end());
+    }
+    output.push('\n');
+}
+
+/// Decides whether two adjacent tokens of a line are separated by a space.
+fn needs_space(prev: &Token, next: &Token) -> bool {
+    if prev.kind == Kind::Comment || next.kind == Kind::Comment {
+        return true;
+    }
+    match (prev.text, next.text) {
+        (_, ")" | "]" | "," | ";" | ":" | "::" | "!" | ">") => false,
+        ("(" | "[" | "::" | "<", _) => false,
+        ("{", "}") => false,
+        // Calls, casts and generic types have no space before the bracket,
+        // unless it starts an expression or a type
+        (_, "(" | "[" | "<") => {
+            matches!(prev.text, "=" | "->" | "=>" | "," | ":" | ";" | "{" | "}")
+                || KEYWORDS.contains(&prev.text)
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_canonical_layout() {
+        let source = "\
+// SPDX-License-Identifier: MIT
+#ifndef FOO_SIMF
+#define FOO_SIMF
+
+
+#define DEFINE_ID(T)  \\
+fn id_##T(x: T) -> T {  x }
+
+/// Docs
+fn add(a:u32,b : u32)->(bool,u32){
+  let (c,   d): (bool,u32) = jet::add_32( a,b );   // trailing
+    let e: [u32; 2] = <(u32, u32)>::into((a,b));
+  let f: (u32, u32)
+= <(u32, u32)>::into(e);
+  match c {
+        true => dbg!(d),
+      false =>
+d,
+  }
+}
+#endif // FOO_SIMF
+";
+        let expected = "\
+// SPDX-License-Identifier: MIT
+#ifndef FOO_SIMF
+#define FOO_SIMF
+
+#define DEFINE_ID(T)  \\
+fn id_##T(x: T) -> T {  x }
+
+/// Docs
+fn add(a: u32, b: u32) -> (bool, u32) {
+    let (c, d): (bool, u32) = jet::add_32(a, b); // trailing
+    let e: [u32; 2] = <(u32, u32)>::into((a, b));
+    let f: (u32, u32)
+        = <(u32, u32)>::into(e);
+    match c {
+        true => dbg!(d),
+        false =>
+            d,
+    }
+}
+#endif // FOO_SIMF
+";
+        let formatted = format_source(source).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format_source(&formatted).unwrap(), expected);
+    }
+
+    #[test]
+    fn restore_nesting_in_conditional_branches() {
+        let source = "\
+#ifdef WIDE
+fn f() -> u64 {
+#else
+fn f() -> u32 {
+#endif
+0
+}
+";
+        let expected = "\
+#ifdef WIDE
+fn f() -> u64 {
+#else
+fn f() -> u32 {
+#endif
+    0
+}
+";
+        assert_eq!(format_source(source).unwrap(), expected);
+    }
+}
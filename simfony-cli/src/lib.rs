@@ -0,0 +1,7 @@
+//! Preprocessing of SimplicityHL sources, shared by the `simfony` CLI and the language server,
+//! and taproot spending of programs, shared by the CLI and the wallet.
+
+pub mod arrays;
+pub mod preprocessor;
+pub mod source_map;
+pub mod taproot;
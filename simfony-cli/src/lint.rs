@@ -0,0 +1,242 @@
+//! Static checks of preprocessed programs for mistakes that the compiler accepts.
+//!
+//! The preprocessed source code contains no comments, so the checks work on its text.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use simfony_cli::source_map::{function_at, Location, Preprocessed};
+
+use crate::testing::TEST_PREFIX;
+
+/// A mistake found in a program.
+pub struct Lint {
+    /// Original location, if the mistake is in the source code
+    pub location: Option<Location>,
+    /// Name of the check, e.g. `unused-function`
+    pub name: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{}: ", location)?;
+        }
+        write!(f, "{} [{}]", self.message, self.name)
+    }
+}
+
+/// Checks a program preprocessed without `TESTING` for:
+///
+/// - `dbg!` calls outside test functions
+/// - functions that are not called from `main` or from a test function
+/// - witness names of the witness file that the program does not read
+/// - `witness::` reads outside `main`
+/// - test functions, which are compiled only because they are outside `#ifdef TESTING`
+///
+/// Functions defined by macro invocations are not reported as unused,
+/// since macros define whole families of helpers.
+pub fn lint(program: &Preprocessed, witness_names: &[String]) -> Vec<Lint> {
+    let functions = program.functions();
+    let lines: Vec<&str> = program.source.lines().collect();
+    let location = |line: usize| program.source_map.location(line).cloned();
+    let mut lints = Vec::new();
+
+    let mut witness_reads = HashSet::new();
+    for (index, text) in lines.iter().enumerate() {
+        let line = index + 1;
+        let function = function_at(&functions, line).map(|function| function.name.as_str());
+        let in_test = function.is_some_and(|name| name.starts_with(TEST_PREFIX));
+        let words: Vec<(usize, &str)> = identifiers(text).collect();
+        for (position, &(offset, word)) in words.iter().enumerate() {
+            let rest = &text[offset + word.len()..];
+            if word == "dbg" && rest.starts_with('!') && !in_test {
+                lints.push(Lint {
+                    location: location(line),
+                    name: "dbg",
+                    message: match function {
+                        Some(name) => format!("`dbg!` left in `{}`", name),
+                        None => "`dbg!` left in the program".to_string(),
+                    },
+                });
+            }
+            if word == "witness" && rest.starts_with("::") {
+                let Some((_, name)) = words.get(position + 1) else {
+                    continue;
+                };
+                witness_reads.insert(*name);
+                if function != Some("main") {
+                    lints.push(Lint {
+                        location: location(line),
+                        name: "witness-outside-main",
+                        message: format!("`witness::{}` is read outside `main`", name),
+                    });
+                }
+            }
+        }
+    }
+
+    for function in &functions {
+        if function.name.starts_with(TEST_PREFIX) {
+            lints.push(Lint {
+                location: location(function.start),
+                name: "test-outside-testing",
+                message: format!(
+                    "Test function `{}` is outside `#ifdef TESTING`",
+                    function.name
+                ),
+            });
+        }
+    }
+
+    // Functions reachable from `main` and the tests through the identifiers of their bodies
+    let indices: HashMap<&str, usize> = functions
+        .iter()
+        .enumerate()
+        .map(|(index, function)| (function.name.as_str(), index))
+        .collect();
+    if indices.contains_key("main") {
+        let mut reached = vec![false; functions.len()];
+        let mut queue: VecDeque<usize> = functions
+            .iter()
+            .enumerate()
+            .filter(|(_, function)| {
+                function.name == "main" || function.name.starts_with(TEST_PREFIX)
+            })
+            .map(|(index, _)| index)
+            .collect();
+        for &index in &queue {
+            reached[index] = true;
+        }
+        while let Some(index) = queue.pop_front() {
+            let function = &functions[index];
+            let body = lines
+                .get(function.start - 1..function.end.min(lines.len()))
+                .unwrap_or_default();
+            for (_, word) in body.iter().flat_map(|text| identifiers(text)) {
+                if let Some(&callee) = indices.get(word) {
+                    if !reached[callee] {
+                        reached[callee] = true;
+                        queue.push_back(callee);
+                    }
+                }
+            }
+        }
+
+        let mut files = SourceFiles::default();
+        for (function, reached) in functions.iter().zip(reached) {
+            let location = location(function.start);
+            let written = location
+                .as_ref()
+                .is_none_or(|location| files.declares(location, &function.name));
+            if !reached && written {
+                lints.push(Lint {
+                    location,
+                    name: "unused-function",
+                    message: format!("Function `{}` is never called", function.name),
+                });
+            }
+        }
+    }
+
+    for name in witness_names {
+        if !witness_reads.contains(name.as_str()) {
+            lints.push(Lint {
+                location: None,
+                name: "unused-witness",
+                message: format!("Witness `{}` is never read", name),
+            });
+        }
+    }
+
+    // Lints without a location come last
+    lints.sort_by_key(|lint| {
+        let location = lint.location.as_ref();
+        (
+            location.is_none(),
+            location.map(|location| (location.file.clone(), location.line)),
+        )
+    });
+    lints
+}
+
+/// Returns the identifiers of a line with their byte offsets.
+fn identifiers(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    text.char_indices()
+        .filter(move |&(offset, c)| is_ident(c) && !text[..offset].ends_with(is_ident))
+        .map(move |(offset, _)| {
+            let len = text[offset..]
+                .find(|c: char| !is_ident(c))
+                .unwrap_or(text.len() - offset);
+            (offset, &text[offset..offset + len])
+        })
+        .filter(|(_, word)| !word.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+/// Lines of the original source files, read on demand.
+#[derive(Default)]
+struct SourceFiles {
+    files: HashMap<Arc<Path>, Vec<String>>,
+}
+
+impl SourceFiles {
+    /// Whether the original line declares the function itself rather than invoking
+    /// a macro that expands to it. Unreadable files are assumed to declare it.
+    fn declares(&mut self, location: &Location, name: &str) -> bool {
+        let lines = self.files.entry(location.file.clone()).or_insert_with(|| {
+            fs::read_to_string(&location.file)
+                .map(|source| source.lines().map(str::to_string).collect())
+                .unwrap_or_default()
+        });
+        lines.get(location.line - 1).is_none_or(|text| {
+            let words: Vec<&str> = identifiers(text).map(|(_, word)| word).collect();
+            words.windows(2).any(|pair| pair == ["fn", name])
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_mistakes() {
+        let source = "\
+fn unused(x: u32) -> u32 {
+    dbg!(x)
+}
+fn helper() -> u32 {
+    witness::A
+}
+fn test_helper() {
+    assert!(jet::eq_32(dbg!(helper()), 1));
+}
+fn main() {
+    let b: u32 = witness::B;
+    assert!(jet::eq_32(helper(), b));
+}
+";
+        let program = Preprocessed::single_file(Path::new("main.simf"), source);
+        let witness_names = ["A", "B", "C"].map(str::to_string);
+
+        let lints: Vec<String> = lint(&program, &witness_names)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            lints,
+            [
+                "main.simf:1: Function `unused` is never called [unused-function]",
+                "main.simf:2: `dbg!` left in `unused` [dbg]",
+                "main.simf:5: `witness::A` is read outside `main` [witness-outside-main]",
+                "main.simf:7: Test function `test_helper` is outside `#ifdef TESTING` [test-outside-testing]",
+                "Witness `C` is never read [unused-witness]",
+            ]
+        );
+    }
+}
@@ -1,17 +1,41 @@
 use anyhow::{Context, Result};
 use base64::display::Base64Display;
 use base64::engine::general_purpose::STANDARD;
-use clap::{Parser, Subcommand};
-use simfony::{dummy_env, Arguments, CompiledProgram, WitnessValues};
+use clap::{Args, Parser, Subcommand};
+use simfony::{dummy_env, Arguments, CompiledProgram, SatisfiedProgram, WitnessValues};
+use simplicity::bit_machine::ExecTracker;
+use simplicity::elements::Transaction;
 use simplicity::ffi::tests::{run_program, TestUpTo};
 use simplicity::human_encoding::Forest;
+use simplicity::jet::elements::ElementsEnv;
+use simplicity::jet::Elements;
 use simplicity::node::CommitNode;
 use simplicity::BitMachine;
 use simplicity::{self, BitIter};
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+use simfony_cli::preprocessor;
+use simfony_cli::source_map::Preprocessed;
+use simfony_cli::taproot;
+
+mod assemble;
+mod coverage;
+mod debugger;
+mod decode;
+mod env;
+mod formatter;
+mod lint;
+mod profiler;
+mod report;
+mod security;
+mod stwo;
+mod testing;
+mod trace;
 mod tracker;
+mod witness;
 
 #[derive(Parser)]
 #[command(name = "simfony")]
@@ -21,6 +45,369 @@ struct Cli {
     command: Commands,
 }
 
+/// Options of the built-in C-style preprocessor
+#[derive(Args, Clone, Default)]
+struct PreprocessorArgs {
+    /// Add a directory to the include search path
+    #[arg(short = 'I', value_name = "DIR")]
+    include_dirs: Vec<PathBuf>,
+
+    /// Define a macro
+    #[arg(short = 'D', value_name = "NAME[=VAL]")]
+    defines: Vec<String>,
+}
+
+impl PreprocessorArgs {
+    /// Creates a preprocessor with the include directories and macros from the command line.
+    fn preprocessor(&self) -> preprocessor::Preprocessor {
+        let mut preprocessor = preprocessor::Preprocessor::new(self.include_dirs.clone());
+        for define in &self.defines {
+            preprocessor.define_arg(define);
+        }
+        preprocessor
+    }
+}
+
+/// Transaction environment in which the program is executed
+#[derive(Args, Clone, Default)]
+struct EnvArgs {
+    /// Spending transaction as hex, or a file with it (default: a dummy transaction)
+    #[arg(long, requires_all = ["utxos", "control-block"])]
+    tx: Option<String>,
+
+    /// Outputs spent by the transaction inputs as a JSON array, or a file with it:
+    /// `[{"script_pubkey": "<hex>", "asset": "<hex>", "value": <sats> | "<hex>"}]`
+    #[arg(long, requires = "tx")]
+    utxos: Option<String>,
+
+    /// Index of the input that spends the program
+    #[arg(long, name = "input-index", default_value_t = 0, requires = "tx")]
+    input_index: u32,
+
+    /// Taproot control block of the input in hex
+    #[arg(long, name = "control-block", requires = "tx")]
+    control_block: Option<String>,
+
+    /// Annex of the input in hex
+    #[arg(long, requires = "tx")]
+    annex: Option<String>,
+
+    /// Genesis block hash of the chain (default: Liquid testnet)
+    #[arg(long, name = "genesis-hash", requires = "tx")]
+    genesis_hash: Option<String>,
+
+    /// JSON or TOML file that describes a synthetic spending transaction
+    #[arg(long, name = "env-spec", conflicts_with = "tx")]
+    env_spec: Option<PathBuf>,
+}
+
+impl EnvArgs {
+    /// Creates the environment for a program with the given CMR.
+    fn env(&self, cmr: simplicity::Cmr) -> Result<ElementsEnv<Arc<Transaction>>> {
+        if let Some(env_spec) = &self.env_spec {
+            return env::EnvSpec::from_file(env_spec)?.to_env(cmr);
+        }
+        let (Some(tx), Some(utxos), Some(control_block)) =
+            (&self.tx, &self.utxos, &self.control_block)
+        else {
+            return Ok(dummy_env::dummy());
+        };
+
+        let tx_bytes = decode::read_bytes(tx, Some(decode::Encoding::Hex))
+            .with_context(|| "Failed to read transaction")?;
+        let utxos_content = if Path::new(utxos).is_file() {
+            fs::read_to_string(utxos)
+                .with_context(|| format!("Failed to read UTXOs file: {}", utxos))?
+        } else {
+            utxos.clone()
+        };
+        let utxos: Vec<env::Output> =
+            serde_json::from_str(&utxos_content).with_context(|| "Failed to parse UTXOs")?;
+
+        env::transaction_env(
+            &tx_bytes,
+            &utxos,
+            self.input_index,
+            cmr,
+            control_block,
+            self.annex.as_deref(),
+            self.genesis_hash.as_deref(),
+        )
+    }
+
+    /// Whether the program runs in the dummy environment.
+    fn is_dummy(&self) -> bool {
+        self.tx.is_none() && self.env_spec.is_none()
+    }
+}
+
+/// Options of the interactive debugger
+#[derive(Args, Clone, Default)]
+struct DebuggerArgs {
+    /// Pause the execution and read debugger commands from stdin
+    #[arg(long, short)]
+    interactive: bool,
+
+    /// Pause on a breakpoint: `fn <name>`, `jet <name>` or `<file>:<line>` of a dbg! site
+    /// (implies --interactive)
+    #[arg(long = "break", short = 'b', value_name = "BREAKPOINT")]
+    breakpoints: Vec<String>,
+
+    /// Path to record the jet calls, dbg! values and branch decisions as JSON lines
+    #[arg(long, value_name = "PATH")]
+    trace: Option<PathBuf>,
+}
+
+/// Encoding of a program and witness given as text
+#[derive(Args, Clone, Default)]
+struct EncodingArgs {
+    /// Read the program and witness as hex
+    #[arg(long, conflicts_with = "base64")]
+    hex: bool,
+
+    /// Read the program and witness as base64
+    #[arg(long)]
+    base64: bool,
+}
+
+impl EncodingArgs {
+    /// Returns the explicit encoding, if any.
+    fn encoding(&self) -> Option<decode::Encoding> {
+        match (self.hex, self.base64) {
+            (true, _) => Some(decode::Encoding::Hex),
+            (_, true) => Some(decode::Encoding::Base64),
+            _ => None,
+        }
+    }
+}
+
+/// Encoded program and witness that spent a program on chain
+#[derive(Args, Clone, Default)]
+struct SpendingArgs {
+    /// Encoded program as hex or base64, or a file with hex, base64 or raw bytes
+    #[arg(long, requires = "encoded-witness", conflicts_with = "tx")]
+    program: Option<String>,
+
+    /// Encoded witness as hex or base64, or a file with hex, base64 or raw bytes
+    #[arg(long = "witness", name = "encoded-witness", requires = "program")]
+    witness: Option<String>,
+
+    /// Spending transaction as hex, or a file with it, to take the encoded program
+    /// and witness from the script witness of an input
+    #[arg(long, required_unless_present = "program")]
+    tx: Option<String>,
+
+    /// Index of the input that spends the program
+    #[arg(long, name = "input-index", default_value_t = 0, requires = "tx")]
+    input_index: u32,
+
+    #[command(flatten)]
+    encoding: EncodingArgs,
+}
+
+impl SpendingArgs {
+    /// Reads the encoded program and witness.
+    fn read(&self) -> Result<(Vec<u8>, Vec<u8>)> {
+        if let Some(tx) = &self.tx {
+            let tx_bytes = decode::read_bytes(tx, Some(decode::Encoding::Hex))
+                .with_context(|| "Failed to read transaction")?;
+            let (witness, program) = decode::spending_stack(&tx_bytes, self.input_index)?;
+            return Ok((program, witness));
+        }
+        let read = |input: &Option<String>, kind: &str| -> Result<Vec<u8>> {
+            let input = input
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("Missing {}", kind))?;
+            decode::read_bytes(input, self.encoding.encoding())
+                .with_context(|| format!("Failed to read {}", kind))
+        };
+        Ok((
+            read(&self.program, "program")?,
+            read(&self.witness, "witness")?,
+        ))
+    }
+}
+
+/// Parameters of the stwo verifier, overriding those of a proof
+#[derive(Args, Clone, Default)]
+struct StwoParameterArgs {
+    /// Log size of the trace
+    #[arg(long)]
+    trace_log_size: Option<u32>,
+
+    /// Log blowup factor of the evaluation domain over the trace
+    #[arg(long)]
+    log_blowup_factor: Option<u32>,
+
+    /// Log degree bound of the last FRI layer (default: 0)
+    #[arg(long)]
+    log_last_layer_degree_bound: Option<u32>,
+
+    /// Number of FRI queries
+    #[arg(long)]
+    n_queries: Option<usize>,
+
+    /// Number of leading zero bits of the proof of work
+    #[arg(long)]
+    pow_bits: Option<u32>,
+
+    /// Number of trace columns of the AIR
+    #[arg(long)]
+    num_columns: Option<usize>,
+}
+
+/// Parameters of a verifier, overriding the constants of its source file
+#[derive(Args, Clone, Default)]
+struct SecurityParameterArgs {
+    /// Log size of the trace (default: `TRACE_LOG_SIZE`)
+    #[arg(long)]
+    trace_log_size: Option<u32>,
+
+    /// Log blowup factor of the evaluation domain over the trace
+    /// (default: `LDE_LOG_SIZE - TRACE_LOG_SIZE`)
+    #[arg(long)]
+    log_blowup_factor: Option<u32>,
+
+    /// Number of FRI queries (default: `NUM_FRI_QUERIES`)
+    #[arg(long)]
+    n_queries: Option<usize>,
+
+    /// Number of leading zero bits of the proof of work (default: from `POW_TARGET_64`)
+    #[arg(long)]
+    pow_bits: Option<u32>,
+
+    /// Modulus of the field of the challenges (default: `FIELD_MODULUS`, or else QM31 of stwo)
+    #[arg(long)]
+    field_modulus: Option<u128>,
+}
+
+impl SecurityParameterArgs {
+    /// Returns the parameters from the command line, or else from the constants of the source.
+    fn parameters(
+        &self,
+        define: impl Fn(&str) -> Result<Option<i128>>,
+    ) -> Result<security::Parameters> {
+        fn pick<T: TryFrom<i128>>(
+            arg: Option<T>,
+            define: Result<Option<i128>>,
+            name: &str,
+            macro_name: &str,
+        ) -> Result<T> {
+            if let Some(arg) = arg {
+                return Ok(arg);
+            }
+            let value = define?.ok_or_else(|| {
+                anyhow::anyhow!("Missing --{} without {} in the source", name, macro_name)
+            })?;
+            T::try_from(value)
+                .map_err(|_| anyhow::anyhow!("{} is out of range: {}", macro_name, value))
+        }
+        let trace_log_size = pick(
+            self.trace_log_size,
+            define("TRACE_LOG_SIZE"),
+            "trace-log-size",
+            "TRACE_LOG_SIZE",
+        )?;
+        let lde_log_size = define("LDE_LOG_SIZE")?;
+        let log_blowup_factor = pick(
+            self.log_blowup_factor,
+            Ok(lde_log_size.map(|lde_log_size| lde_log_size - i128::from(trace_log_size))),
+            "log-blowup-factor",
+            "LDE_LOG_SIZE",
+        )?;
+        let n_queries = pick(
+            self.n_queries,
+            define("NUM_FRI_QUERIES"),
+            "n-queries",
+            "NUM_FRI_QUERIES",
+        )?;
+        let pow_bits = match self.pow_bits {
+            Some(pow_bits) => pow_bits,
+            None => match define("POW_TARGET_64")? {
+                Some(target) => security::pow_bits(target)?,
+                None => anyhow::bail!("Missing --pow-bits without POW_TARGET_64 in the source"),
+            },
+        };
+        let field_modulus = match self.field_modulus {
+            Some(field_modulus) => field_modulus,
+            None => match define("FIELD_MODULUS")? {
+                Some(modulus) => u128::try_from(modulus)
+                    .map_err(|_| anyhow::anyhow!("FIELD_MODULUS is out of range: {}", modulus))?,
+                None => security::QM31_MODULUS,
+            },
+        };
+        Ok(security::Parameters {
+            trace_log_size,
+            log_blowup_factor,
+            n_queries,
+            pow_bits,
+            field_modulus,
+        })
+    }
+}
+
+impl StwoParameterArgs {
+    /// Returns the parameters of the proof with the ones from the command line.
+    fn config(&self, proof: Option<stwo::Config>) -> Result<stwo::Config> {
+        fn pick<T: Copy>(arg: Option<T>, proof: Option<T>, name: &str) -> Result<T> {
+            arg.or(proof)
+                .ok_or_else(|| anyhow::anyhow!("Missing --{} without a proof", name))
+        }
+        Ok(stwo::Config {
+            trace_log_size: pick(
+                self.trace_log_size,
+                proof.map(|config| config.trace_log_size),
+                "trace-log-size",
+            )?,
+            log_blowup_factor: pick(
+                self.log_blowup_factor,
+                proof.map(|config| config.log_blowup_factor),
+                "log-blowup-factor",
+            )?,
+            log_last_layer_degree_bound: self
+                .log_last_layer_degree_bound
+                .or(proof.map(|config| config.log_last_layer_degree_bound))
+                .unwrap_or_default(),
+            n_queries: pick(
+                self.n_queries,
+                proof.map(|config| config.n_queries),
+                "n-queries",
+            )?,
+            pow_bits: pick(
+                self.pow_bits,
+                proof.map(|config| config.pow_bits),
+                "pow-bits",
+            )?,
+            num_columns: pick(
+                self.num_columns,
+                proof.map(|config| config.num_columns),
+                "num-columns",
+            )?,
+        })
+    }
+}
+
+/// Outputs of `simfony build` for spending a satisfied program
+#[derive(Args, Clone, Default)]
+struct WitnessOutputArgs {
+    /// Path to write the encoded witness, in the output format (hex for simpl)
+    #[arg(long, name = "witness-output-path", requires = "witness")]
+    witness_output_path: Option<PathBuf>,
+
+    /// Path to write the taproot script witness stack as a JSON array of hex strings:
+    /// witness, program, CMR script, control block and annex padding
+    #[arg(
+        long,
+        name = "witness-stack-path",
+        requires_all = ["witness", "internal-key"]
+    )]
+    witness_stack_path: Option<PathBuf>,
+
+    /// X-only internal key of the taproot output in hex
+    #[arg(long, name = "internal-key")]
+    internal_key: Option<String>,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Build a Simfony program
@@ -43,6 +430,19 @@ enum Commands {
         /// - simpl: Disassembled Simplicity source code
         #[arg(long, name = "output-format", default_value = "base64")]
         output_format: String,
+
+        /// Print a JSON summary of the program instead of text
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        witness_output: WitnessOutputArgs,
+
+        #[command(flatten)]
+        env: EnvArgs,
+
+        #[command(flatten)]
+        preprocessor: PreprocessorArgs,
     },
 
     /// Run a Simfony program
@@ -57,6 +457,16 @@ enum Commands {
         /// Path to file with arguments
         #[arg(long)]
         param: Option<PathBuf>,
+
+        /// Print a JSON summary of the program instead of text
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        env: EnvArgs,
+
+        #[command(flatten)]
+        preprocessor: PreprocessorArgs,
     },
 
     /// Debug a Simfony program
@@ -71,9 +481,329 @@ enum Commands {
         /// Path to file with arguments
         #[arg(long)]
         param: Option<PathBuf>,
+
+        #[command(flatten)]
+        debugger: DebuggerArgs,
+
+        /// Print a JSON summary of the program and its result,
+        /// writing the debug output to stderr
+        #[arg(long, conflicts_with_all = ["interactive", "breakpoints"])]
+        json: bool,
+
+        #[command(flatten)]
+        env: EnvArgs,
+
+        #[command(flatten)]
+        preprocessor: PreprocessorArgs,
+    },
+
+    /// Profile the jet calls of a Simfony program
+    Profile {
+        /// Path to the source file
+        path: PathBuf,
+
+        /// Path to the witness file
+        #[arg(long)]
+        witness: Option<PathBuf>,
+
+        /// Path to file with arguments
+        #[arg(long)]
+        param: Option<PathBuf>,
+
+        /// Path to write the call stacks in the folded format of flamegraph tools
+        #[arg(long, value_name = "PATH")]
+        folded: Option<PathBuf>,
+
+        /// Number of rows to show in each table
+        #[arg(long, default_value_t = 20)]
+        top: usize,
+
+        #[command(flatten)]
+        preprocessor: PreprocessorArgs,
+    },
+
+    /// Assemble a program from the Simplicity human encoding
+    Assemble {
+        /// Path to the `.simpl` file
+        path: PathBuf,
+
+        /// Path to a JSON file that maps witness names to hex values
+        #[arg(long)]
+        witness: Option<PathBuf>,
+
+        /// Path to write the assembled program
+        #[arg(long, name = "output-path")]
+        output_path: Option<PathBuf>,
+
+        /// Path to write the encoded witness
+        #[arg(long, name = "witness-output-path", requires = "witness")]
+        witness_output_path: Option<PathBuf>,
+
+        /// Output format
+        ///
+        /// - base64: Base64 encoding
+        /// - hex: Hex encoding
+        #[arg(long, name = "output-format", default_value = "base64")]
+        output_format: String,
+    },
+
+    /// Decode an encoded Simplicity program
+    Decode {
+        /// Program as hex or base64, or a file with hex, base64 or raw bytes
+        program: String,
+
+        /// Witness as hex or base64, or a file with hex, base64 or raw bytes
+        #[arg(long)]
+        witness: Option<String>,
+
+        /// Print the disassembled Simplicity source code
+        #[arg(long)]
+        simpl: bool,
+
+        #[command(flatten)]
+        encoding: EncodingArgs,
+    },
+
+    /// Compare two traces recorded by `simfony debug --trace` and report the first divergence
+    TraceDiff {
+        /// Path to the first trace
+        a: PathBuf,
+
+        /// Path to the second trace
+        b: PathBuf,
+
+        /// Ignore the branch decisions, e.g. to compare different versions of a program
+        #[arg(long, name = "skip-branches")]
+        skip_branches: bool,
+    },
+
+    /// Preprocess a source file, e.g. for tools that read plain SimplicityHL
+    Preprocess {
+        /// Path to the source file
+        path: PathBuf,
+
+        /// Path to write the preprocessed source file
+        #[arg(long, name = "output-path")]
+        output_path: Option<PathBuf>,
+
+        #[command(flatten)]
+        preprocessor: PreprocessorArgs,
+    },
+
+    /// Run `fn test_*` functions found in the source files
+    Test {
+        /// Run only the tests whose name contains this string
+        filter: Option<String>,
+
+        /// Directory with the source files
+        #[arg(long, default_value = "src")]
+        dir: PathBuf,
+
+        /// Number of tests to run in parallel
+        #[arg(long, short)]
+        jobs: Option<usize>,
+
+        /// Wrap the function calls that initialize `let` bindings in `dbg!`,
+        /// so that failing tests print the values they computed
+        #[arg(long)]
+        debug: bool,
+
+        /// Environment spec for the tests without a `<file>.env.toml` or `<file>.env.json`
+        #[arg(long, name = "env-spec")]
+        env_spec: Option<PathBuf>,
+
+        /// Path to write the branch coverage of the tests as an lcov tracefile
+        #[arg(long, value_name = "PATH")]
+        coverage: Option<PathBuf>,
+
+        #[command(flatten)]
+        preprocessor: PreprocessorArgs,
+    },
+
+    /// Check the witness and arguments files of a program against its declared types
+    CheckWitness {
+        /// Path to the source file
+        path: PathBuf,
+
+        /// Path to the witness file
+        #[arg(long)]
+        witness: Option<PathBuf>,
+
+        /// Path to file with arguments
+        #[arg(long)]
+        param: Option<PathBuf>,
+
+        #[command(flatten)]
+        preprocessor: PreprocessorArgs,
+    },
+
+    /// Decode the encoded witness that spent a program into a witness file
+    DecodeWitness {
+        /// Path to the source file
+        path: PathBuf,
+
+        #[command(flatten)]
+        spending: SpendingArgs,
+
+        /// Path to file with arguments
+        #[arg(long)]
+        param: Option<PathBuf>,
+
+        /// Path to write the witness file
+        #[arg(long, name = "output-path")]
+        output_path: Option<PathBuf>,
+
+        /// Witness file to compare the decoded values with, e.g. an archived proof
+        #[arg(long)]
+        compare: Option<PathBuf>,
+
+        #[command(flatten)]
+        preprocessor: PreprocessorArgs,
+    },
+
+    /// Print a witness file with a placeholder value for every `witness::NAME` of a program
+    WitnessTemplate {
+        /// Path to the source file
+        path: PathBuf,
+
+        /// Path to write the witness file, printing the witness names and their types instead
+        #[arg(long, name = "output-path")]
+        output_path: Option<PathBuf>,
+
+        #[command(flatten)]
+        preprocessor: PreprocessorArgs,
+    },
+
+    /// Convert a stwo proof in JSON into a witness file of the stwo verifier
+    StwoWitness {
+        /// Path to the proof, e.g. stwo-verifier/tests/data/proof.json
+        proof: PathBuf,
+
+        /// Path to write the witness file
+        #[arg(long, name = "output-path")]
+        output_path: Option<PathBuf>,
+    },
+
+    /// Generate `config.simf` of the stwo verifier from the parameters of a proof
+    StwoConfig {
+        /// Path to the proof to take the parameters from, e.g. stwo-verifier/tests/data/proof.json
+        proof: Option<PathBuf>,
+
+        /// Path to the proof to take the parameters of the `TESTING` build from
+        #[arg(long)]
+        testing: Option<PathBuf>,
+
+        #[command(flatten)]
+        parameters: StwoParameterArgs,
+
+        /// Path to write the header, e.g. stwo-verifier/src/config.simf
+        #[arg(long, name = "output-path")]
+        output_path: Option<PathBuf>,
+    },
+
+    /// Check that a stwo proof matches the constants of `config.simf` that the verifier
+    /// is built with: `LDE_LOG_SIZE`, `TRACE_LOG_SIZE`, `NUM_FRI_QUERIES`, `NUM_FRI_LAYERS`,
+    /// `NUM_COLUMNS` and `POW_TARGET_64`
+    StwoCheck {
+        /// Path to the proof, e.g. stwo-verifier/tests/data/proof.json
+        proof: PathBuf,
+
+        /// Path to the source file of the verifier, e.g. stwo-verifier/src/main.simf
+        path: PathBuf,
+
+        #[command(flatten)]
+        preprocessor: PreprocessorArgs,
+    },
+
+    /// Report the conjectured and proven bits of security of a verifier, from the constants
+    /// `TRACE_LOG_SIZE`, `LDE_LOG_SIZE`, `NUM_FRI_QUERIES` and `POW_TARGET_64` of its source
+    /// or from the command line
+    Security {
+        /// Path to the source file of the verifier, e.g. stwo-verifier/src/main.simf
+        path: Option<PathBuf>,
+
+        #[command(flatten)]
+        parameters: SecurityParameterArgs,
+
+        /// Fail if the conjectured security is below this many bits
+        #[arg(long)]
+        min_bits: Option<u32>,
+
+        /// Fail if the proven security is below this many bits
+        #[arg(long)]
+        min_proven_bits: Option<u32>,
+
+        #[command(flatten)]
+        preprocessor: PreprocessorArgs,
+    },
+
+    /// Check a program for mistakes that the compiler accepts, such as `dbg!` calls,
+    /// unused functions and witness names, or test functions outside `#ifdef TESTING`
+    ///
+    /// `TESTING` is never defined, so that the code that gets deployed is checked.
+    Lint {
+        /// Path to the source file
+        path: PathBuf,
+
+        /// Path to the witness file, to report the witness names that the program does not read
+        #[arg(long)]
+        witness: Option<PathBuf>,
+
+        #[command(flatten)]
+        preprocessor: PreprocessorArgs,
+    },
+
+    /// Format source files in place
+    ///
+    /// Lines are re-indented and their tokens re-spaced, but line breaks are kept as written:
+    /// long lines are not wrapped and wrapped statements are not joined, so code that is
+    /// wrapped differently passes `--check`.
+    Fmt {
+        /// Source files or directories with source files
+        #[arg(default_value = "src")]
+        paths: Vec<PathBuf>,
+
+        /// Only check that the files are formatted, without changing them
+        #[arg(long)]
+        check: bool,
     },
 }
 
+/// Reads and preprocesses a source file.
+fn load_source(path: &Path, preprocessor: &PreprocessorArgs) -> Result<Preprocessed> {
+    preprocessor
+        .preprocessor()
+        .preprocess_file(path)
+        .with_context(|| format!("Failed to preprocess source file: {}", path.display()))
+}
+
+/// Compiles a preprocessed program, reporting errors at their original locations.
+fn compile_program(program: &Preprocessed, arguments: Arguments) -> Result<CompiledProgram> {
+    CompiledProgram::new(program.source.as_str(), arguments, true)
+        .map_err(|e| anyhow::anyhow!(program.source_map.rewrite_error(&e)))
+}
+
+/// Checks the witness and the arguments against the declared types of a program,
+/// reporting every problem with its path in the value.
+fn check_inputs(program: &Preprocessed, witness: Option<&str>, param: Option<&str>) -> Result<()> {
+    let declarations = witness::declarations(program)?;
+    let mut problems = Vec::new();
+    for (kind, content, declared) in [
+        ("witness", witness, &declarations.witnesses),
+        ("param", param, &declarations.parameters),
+    ] {
+        if let Some(content) = content {
+            for problem in witness::check(content, declared) {
+                problems.push(format!("{} {}", kind, problem));
+            }
+        }
+    }
+    if !problems.is_empty() {
+        anyhow::bail!("Invalid inputs:\n  {}", problems.join("\n  "));
+    }
+    Ok(())
+}
+
 fn parse_witness(content: Option<&str>) -> Result<WitnessValues> {
     content.map_or(Ok(WitnessValues::default()), |s| {
         serde_json::from_str(s).with_context(|| "Failed to parse witness")
@@ -118,51 +848,110 @@ fn write_build_output(
     Ok(())
 }
 
+/// Encodes witness bytes in the output format, using hex for the `simpl` format.
+fn encode_witness(witness_bytes: &[u8], output_format: &str) -> String {
+    match output_format {
+        "hex" | "simpl" => hex::encode(witness_bytes),
+        _ => Base64Display::new(witness_bytes, &STANDARD).to_string(),
+    }
+}
+
+/// Writes the encoded witness and the taproot script witness stack.
+fn write_witness_output(
+    node: &simplicity::RedeemNode<Elements>,
+    witness_output: &WitnessOutputArgs,
+    output_format: &str,
+) -> Result<()> {
+    if let Some(path) = &witness_output.witness_output_path {
+        let (_, witness_bytes) = node.encode_to_vec();
+        fs::write(path, encode_witness(&witness_bytes, output_format))
+            .with_context(|| format!("Failed to write output file: {}", path.display()))?;
+    }
+
+    if let (Some(path), Some(internal_key)) = (
+        &witness_output.witness_stack_path,
+        &witness_output.internal_key,
+    ) {
+        let internal_key = taproot::parse_internal_key(internal_key)?;
+        let stack: Vec<String> = taproot::witness_stack(node, internal_key)?
+            .iter()
+            .map(hex::encode)
+            .collect();
+        fs::write(path, serde_json::to_string_pretty(&stack)?)
+            .with_context(|| format!("Failed to write output file: {}", path.display()))?;
+    }
+    Ok(())
+}
+
 fn handle_build(
     path: PathBuf,
     witness: Option<PathBuf>,
     output_path: Option<PathBuf>,
     output_format: String,
+    json: bool,
+    witness_output: WitnessOutputArgs,
+    env_args: EnvArgs,
+    preprocessor: PreprocessorArgs,
 ) -> Result<()> {
-    let source = fs::read_to_string(&path)
-        .with_context(|| format!("Failed to read source file: {}", path.display()))?;
+    let program = load_source(&path, &preprocessor)?;
 
-    let compiled = CompiledProgram::new(source, Arguments::default(), true)
-        .map_err(|e| anyhow::anyhow!(e))
+    let compiled = compile_program(&program, Arguments::default())
         .with_context(|| "Failed to compile program")?;
 
     if let Some(witness_path) = witness {
         let witness_content = fs::read_to_string(&witness_path)
             .with_context(|| format!("Failed to read witness file: {}", witness_path.display()))?;
 
+        check_inputs(&program, Some(&witness_content), None)?;
         let witness = parse_witness(Some(&witness_content))?;
-        let satisfied = compiled.satisfy(witness).map_err(|e| anyhow::anyhow!(e))?;
+        // Branches that are not taken in the spending environment are pruned,
+        // as a node requires of the spent program
+        let env = env_args.env(compiled.commit().cmr())?;
+        let satisfied = compiled
+            .satisfy_with_env(witness, Some(&env))
+            .map_err(|e| anyhow::anyhow!(e))?;
 
         let node = satisfied.redeem();
-        println!("Node bounds: {:?}", node.bounds());
+        let report = report::Report::redeem(node);
+        if json {
+            report.print()?;
+        } else {
+            println!("Node bounds: {:?}", node.bounds());
+            println!("Program size: {}", report.program_size);
+            println!("Witness size: {}", report.witness_size.unwrap_or_default());
+            println!("Padding size: {}", report.padding_size.unwrap_or_default());
+        }
 
-        let (program_bytes, witness_bytes) = node.encode_to_vec();
-        println!("Program size: {}", program_bytes.len());
-        println!("Witness size: {}", witness_bytes.len());
-
-        let padding_size = node
-            .bounds()
-            .cost
-            .get_padding(&vec![witness_bytes, program_bytes.clone()])
-            .unwrap_or_default()
-            .len();
-        println!("Padding size: {}", padding_size);
-
-        write_build_output(output_path, program_bytes, output_format)
+        write_witness_output(node, &witness_output, &output_format)?;
+
+        let (program_bytes, _) = node.encode_to_vec();
+        if !json || output_path.is_some() {
+            write_build_output(output_path, program_bytes, output_format)?;
+        }
+        Ok(())
     } else {
-        let program_bytes = compiled.commit().encode_to_vec();
-        write_build_output(output_path, program_bytes, output_format)
+        let commit = compiled.commit();
+        if json {
+            report::Report::commit(&commit).print()?;
+        }
+
+        let program_bytes = commit.encode_to_vec();
+        if !json || output_path.is_some() {
+            write_build_output(output_path, program_bytes, output_format)?;
+        }
+        Ok(())
     }
 }
 
-fn handle_run(path: PathBuf, witness: Option<PathBuf>, param: Option<PathBuf>) -> Result<()> {
-    let source = fs::read_to_string(&path)
-        .with_context(|| format!("Failed to read source file: {}", path.display()))?;
+fn handle_run(
+    path: PathBuf,
+    witness: Option<PathBuf>,
+    param: Option<PathBuf>,
+    json: bool,
+    env_args: EnvArgs,
+    preprocessor: PreprocessorArgs,
+) -> Result<()> {
+    let program = load_source(&path, &preprocessor)?;
 
     let param_content =
         if let Some(param_path) = param {
@@ -182,35 +971,56 @@ fn handle_run(path: PathBuf, witness: Option<PathBuf>, param: Option<PathBuf>) -
             None
         };
 
+    // Missing files are checked as empty, to report the names they should contain
+    check_inputs(
+        &program,
+        Some(witness_content.as_deref().unwrap_or("{}")),
+        Some(param_content.as_deref().unwrap_or("{}")),
+    )?;
     let arguments = parse_arguments(param_content.as_deref())?;
-    let compiled = CompiledProgram::new(source, arguments, true).map_err(|e| anyhow::anyhow!(e))?;
+    let compiled = compile_program(&program, arguments)?;
     let witness = parse_witness(witness_content.as_deref())?;
+    let env = env_args.env(compiled.commit().cmr())?;
     let satisfied = compiled
-        .satisfy_with_env(witness, Some(&dummy_env::dummy()))
+        .satisfy_with_env(witness, Some(&env))
         .map_err(|e| anyhow::anyhow!(e))?;
 
     let node = satisfied.redeem();
-    println!("Node bounds: {:?}", node.bounds());
-
-    let (program_bytes, witness_bytes) = node.encode_to_vec();
-
-    let padding_size = node
-        .bounds()
-        .cost
-        .get_padding(&vec![witness_bytes.clone(), program_bytes.clone()])
-        .unwrap_or_default()
-        .len();
-    println!("Padding size: {}", padding_size);
+    let report = report::Report::redeem(node);
+    if !json {
+        println!("Node bounds: {:?}", node.bounds());
+        println!("Padding size: {}", report.padding_size.unwrap_or_default());
+    }
 
-    let _ = run_program(&program_bytes, &witness_bytes, TestUpTo::Everything)
-        .map_err(|e| anyhow::anyhow!("Failed to run program: {}", e))?;
+    if !env_args.is_dummy() {
+        // The reference implementation is run without a transaction environment,
+        // so the program is executed on the Bit Machine as a node validating the input would
+        let mut machine = BitMachine::for_program(node)?;
+        machine
+            .exec(node, &env)
+            .map_err(|e| anyhow::anyhow!("Failed to run program: {}", e))?;
+    } else {
+        let (program_bytes, witness_bytes) = node.encode_to_vec();
+        let _ = run_program(&program_bytes, &witness_bytes, TestUpTo::Everything)
+            .map_err(|e| anyhow::anyhow!("Failed to run program: {}", e))?;
+    }
 
+    if json {
+        report.print()?;
+    }
     Ok(())
 }
 
-fn handle_debug(path: PathBuf, witness: Option<PathBuf>, param: Option<PathBuf>) -> Result<()> {
-    let source = fs::read_to_string(&path)
-        .with_context(|| format!("Failed to read source file: {}", path.display()))?;
+fn handle_debug(
+    path: PathBuf,
+    witness: Option<PathBuf>,
+    param: Option<PathBuf>,
+    debugger: DebuggerArgs,
+    json: bool,
+    env_args: EnvArgs,
+    preprocessor: PreprocessorArgs,
+) -> Result<()> {
+    let program = load_source(&path, &preprocessor)?;
 
     let param_content =
         if let Some(param_path) = param {
@@ -231,21 +1041,583 @@ fn handle_debug(path: PathBuf, witness: Option<PathBuf>, param: Option<PathBuf>)
         };
 
     let arguments = parse_arguments(param_content.as_deref())?;
-    let compiled = CompiledProgram::new(source, arguments, true).map_err(|e| anyhow::anyhow!(e))?;
     let witness = parse_witness(witness_content.as_deref())?;
-    let satisfied = compiled.satisfy(witness).map_err(|e| anyhow::anyhow!(e))?;
+    if json {
+        let (satisfied, env) = satisfy_program(&program, arguments, witness, &env_args)?;
+        let mut report = report::Report::redeem(satisfied.redeem());
+        let mut output = std::io::stderr();
+        let mut tracker = tracker::Tracker::new(satisfied.debug_symbols(), &program, &mut output);
+        let res = execute_traced(
+            &satisfied,
+            &env,
+            &program,
+            debugger.trace.as_deref(),
+            &mut tracker,
+        )?;
+        report.result = Some(res.to_string());
+        return report.print();
+    }
+    if !debugger.interactive && debugger.breakpoints.is_empty() {
+        return debug_program(
+            &program,
+            arguments,
+            witness,
+            &env_args,
+            debugger.trace.as_deref(),
+            None,
+            &mut std::io::stdout(),
+        );
+    }
+
+    let breakpoints = debugger::parse_breakpoints(&debugger.breakpoints)?;
+    let trace_path = debugger.trace;
+    let (satisfied, env) = satisfy_program(&program, arguments, witness, &env_args)?;
+    let mut output = std::io::stdout();
+    let mut debugger = debugger::Debugger::new(
+        satisfied.debug_symbols(),
+        &program,
+        breakpoints,
+        std::io::stdin().lock(),
+        &mut output,
+    );
+    let res = execute_traced(
+        &satisfied,
+        &env,
+        &program,
+        trace_path.as_deref(),
+        &mut debugger,
+    )?;
+    println!("Result: {}", res);
+    Ok(())
+}
+
+/// Compiles a program and satisfies it with the witness values,
+/// pruning the branches that are not taken in the environment.
+fn satisfy_program(
+    program: &Preprocessed,
+    arguments: Arguments,
+    witness: WitnessValues,
+    env_args: &EnvArgs,
+) -> Result<(SatisfiedProgram, ElementsEnv<Arc<Transaction>>)> {
+    let compiled = compile_program(program, arguments)?;
+    let env = env_args.env(compiled.commit().cmr())?;
+    let satisfied = compiled
+        .satisfy_with_env(witness, Some(&env))
+        .map_err(|e| anyhow::anyhow!(e))?;
+    Ok((satisfied, env))
+}
+
+/// Executes a satisfied program on the Bit Machine.
+fn execute_program<T: ExecTracker<Elements>>(
+    satisfied: &SatisfiedProgram,
+    env: &ElementsEnv<Arc<Transaction>>,
+    tracker: &mut T,
+) -> Result<simplicity::Value> {
     let node = satisfied.redeem();
-    println!("Node bounds: {:?}", node.bounds());
     let mut machine = BitMachine::for_program(node)?;
-    let env = dummy_env::dummy();
-    let mut tracker = tracker::Tracker {
-        debug_symbols: satisfied.debug_symbols(),
+    Ok(machine.exec_with_tracker(node, env, tracker)?)
+}
+
+/// Executes a satisfied program on the Bit Machine,
+/// recording the execution to a trace file if a path is given.
+fn execute_traced<T: ExecTracker<Elements>>(
+    satisfied: &SatisfiedProgram,
+    env: &ElementsEnv<Arc<Transaction>>,
+    program: &Preprocessed,
+    trace_path: Option<&Path>,
+    tracker: &mut T,
+) -> Result<simplicity::Value> {
+    let Some(trace_path) = trace_path else {
+        return execute_program(satisfied, env, tracker);
     };
-    let res = machine.exec_with_tracker(node, &env, &mut tracker)?;
+    let file = fs::File::create(trace_path)
+        .with_context(|| format!("Failed to create trace file: {}", trace_path.display()))?;
+    let mut recorder = trace::TraceRecorder::new(
+        satisfied.debug_symbols(),
+        program,
+        std::io::BufWriter::new(file),
+        tracker,
+    );
+    let res = execute_program(satisfied, env, &mut recorder)?;
+    recorder
+        .finish()
+        .with_context(|| format!("Failed to write trace file: {}", trace_path.display()))?;
+    Ok(res)
+}
 
-    println!("Result: {}", res);
+/// Compiles a program and executes it on the Bit Machine,
+/// writing the tracked jet calls and debug values to the output.
+fn debug_program<W: Write>(
+    program: &Preprocessed,
+    arguments: Arguments,
+    witness: WitnessValues,
+    env: &EnvArgs,
+    trace_path: Option<&Path>,
+    coverage: Option<&mut coverage::Coverage>,
+    output: &mut W,
+) -> Result<()> {
+    let compiled = compile_program(program, arguments)?;
+    let env = env.env(compiled.commit().cmr())?;
+    let satisfied = compiled
+        .satisfy_with_env(witness, Some(&env))
+        .map_err(|e| anyhow::anyhow!(e))?;
+    writeln!(output, "Node bounds: {:?}", satisfied.redeem().bounds())?;
+    let mut tracker = tracker::Tracker::new(satisfied.debug_symbols(), program, &mut *output);
+    let res = match coverage {
+        Some(coverage) => {
+            let mut recorder = coverage::BranchRecorder::new(&mut tracker);
+            let res = execute_traced(&satisfied, &env, program, trace_path, &mut recorder);
+            // Branches that ran before a failure are covered too
+            coverage.add(
+                program,
+                &compiled,
+                satisfied.redeem(),
+                satisfied.debug_symbols(),
+                recorder.counts(),
+            );
+            res?
+        }
+        None => execute_traced(&satisfied, &env, program, trace_path, &mut tracker)?,
+    };
+
+    writeln!(output, "Result: {}", res)?;
+    Ok(())
+}
+
+fn handle_profile(
+    path: PathBuf,
+    witness: Option<PathBuf>,
+    param: Option<PathBuf>,
+    folded: Option<PathBuf>,
+    top: usize,
+    preprocessor: PreprocessorArgs,
+) -> Result<()> {
+    let program = load_source(&path, &preprocessor)?;
+
+    let param_content =
+        if let Some(param_path) = param {
+            Some(fs::read_to_string(&param_path).with_context(|| {
+                format!("Failed to read parameter file: {}", param_path.display())
+            })?)
+        } else {
+            None
+        };
+
+    let witness_content =
+        if let Some(witness_path) = witness {
+            Some(fs::read_to_string(&witness_path).with_context(|| {
+                format!("Failed to read witness file: {}", witness_path.display())
+            })?)
+        } else {
+            None
+        };
+
+    let arguments = parse_arguments(param_content.as_deref())?;
+    let witness = parse_witness(witness_content.as_deref())?;
+    let (satisfied, env) = satisfy_program(&program, arguments, witness, &EnvArgs::default())?;
+    let bounds = satisfied.redeem().bounds();
+
+    let mut profiler = profiler::Profiler::new(satisfied.debug_symbols(), &program);
+    execute_program(&satisfied, &env, &mut profiler)?;
+
+    println!("Program cost: {}", bounds.cost);
+    println!("Jet cost: {} mWU\n", profiler.total_cost());
+    profiler.write_tables(&mut std::io::stdout(), top)?;
+
+    if let Some(folded_path) = folded {
+        let mut file = fs::File::create(&folded_path)
+            .with_context(|| format!("Failed to write output file: {}", folded_path.display()))?;
+        profiler.write_folded(&mut file)?;
+    }
+    Ok(())
+}
+
+fn handle_assemble(
+    path: PathBuf,
+    witness: Option<PathBuf>,
+    output_path: Option<PathBuf>,
+    witness_output_path: Option<PathBuf>,
+    output_format: String,
+) -> Result<()> {
+    let source = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read source file: {}", path.display()))?;
+
+    let witness_content =
+        if let Some(witness_path) = witness {
+            Some(fs::read_to_string(&witness_path).with_context(|| {
+                format!("Failed to read witness file: {}", witness_path.display())
+            })?)
+        } else {
+            None
+        };
+
+    let (program_bytes, witness_bytes) = assemble::assemble(&source, witness_content.as_deref())
+        .with_context(|| "Failed to assemble program")?;
+
+    if let Some(witness_bytes) = witness_bytes {
+        let witness_output = encode_witness(&witness_bytes, &output_format);
+        match witness_output_path {
+            Some(path) => fs::write(&path, witness_output)
+                .with_context(|| format!("Failed to write output file: {}", path.display()))?,
+            None => println!("Witness:\n{}", witness_output),
+        }
+    }
+    write_build_output(output_path, program_bytes, output_format)
+}
+
+fn handle_decode(
+    program: String,
+    witness: Option<String>,
+    simpl: bool,
+    encoding: EncodingArgs,
+) -> Result<()> {
+    let encoding = encoding.encoding();
+    let program_bytes =
+        decode::read_bytes(&program, encoding).with_context(|| "Failed to read program")?;
+    let witness_bytes = witness
+        .map(|witness| {
+            decode::read_bytes(&witness, encoding).with_context(|| "Failed to read witness")
+        })
+        .transpose()?;
+
+    decode::print_program(&program_bytes, witness_bytes.as_deref())?;
+    if simpl {
+        println!();
+        write_build_output(None, program_bytes, "simpl".to_string())?;
+    }
+    Ok(())
+}
+
+fn handle_preprocess(
+    path: PathBuf,
+    output_path: Option<PathBuf>,
+    preprocessor: PreprocessorArgs,
+) -> Result<()> {
+    let program = load_source(&path, &preprocessor)?;
+    match output_path {
+        Some(path) => {
+            fs::write(&path, &program.source)
+                .with_context(|| format!("Failed to write output file: {}", path.display()))?;
+        }
+        None => {
+            print!("{}", program.source);
+        }
+    }
+    Ok(())
+}
+
+fn handle_test(
+    filter: Option<String>,
+    dir: PathBuf,
+    jobs: Option<usize>,
+    debug: bool,
+    env_spec: Option<PathBuf>,
+    coverage_path: Option<PathBuf>,
+    preprocessor: PreprocessorArgs,
+) -> Result<()> {
+    let tests = testing::discover_tests(&dir, &preprocessor, env_spec.as_deref())?;
+    let jobs = jobs.unwrap_or_else(|| {
+        std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+    });
+    let coverage = coverage_path
+        .as_ref()
+        .map(|_| coverage::Coverage::default());
+    let (summary, coverage) = testing::run_tests(tests, filter.as_deref(), jobs, debug, coverage);
+    if let (Some(path), Some(coverage)) = (coverage_path, coverage) {
+        let mut file = fs::File::create(&path)
+            .with_context(|| format!("Failed to create coverage file: {}", path.display()))?;
+        coverage
+            .write_lcov(&mut file)
+            .with_context(|| format!("Failed to write coverage file: {}", path.display()))?;
+        println!("Coverage written to {}", path.display());
+        let unmapped = coverage.unmapped();
+        if unmapped > 0 {
+            eprintln!(
+                "Warning: {} branch(es) could not be located by a call on a single line \
+                 and are reported at the first line of their call or at `fn main`",
+                unmapped
+            );
+        }
+    }
+    if summary.failed > 0 {
+        anyhow::bail!("{} test(s) failed", summary.failed);
+    }
+    Ok(())
+}
+
+fn handle_check_witness(
+    path: PathBuf,
+    witness: Option<PathBuf>,
+    param: Option<PathBuf>,
+    preprocessor: PreprocessorArgs,
+) -> Result<()> {
+    let program = load_source(&path, &preprocessor)?;
+    let read = |path: Option<PathBuf>, kind: &str| -> Result<String> {
+        match path {
+            Some(path) => fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {} file: {}", kind, path.display())),
+            None => Ok("{}".to_string()),
+        }
+    };
+    let witness_content = read(witness, "witness")?;
+    let param_content = read(param, "parameter")?;
+    check_inputs(&program, Some(&witness_content), Some(&param_content))?;
+    println!("Witness and arguments match the declared types");
     Ok(())
 }
+
+fn handle_decode_witness(
+    path: PathBuf,
+    spending: SpendingArgs,
+    param: Option<PathBuf>,
+    output_path: Option<PathBuf>,
+    compare: Option<PathBuf>,
+    preprocessor: PreprocessorArgs,
+) -> Result<()> {
+    let program = load_source(&path, &preprocessor)?;
+    let param_content = param
+        .map(|param_path| {
+            fs::read_to_string(&param_path)
+                .with_context(|| format!("Failed to read parameter file: {}", param_path.display()))
+        })
+        .transpose()?;
+    let compiled = compile_program(&program, parse_arguments(param_content.as_deref())?)?;
+    let declared = witness::declarations(&program)?.witnesses;
+
+    let (program_bytes, witness_bytes) = spending.read()?;
+    let decoded = witness::decode(&compiled, &declared, &program_bytes, &witness_bytes)?;
+    for name in &decoded.pruned {
+        eprintln!(
+            "Warning: witness {} is pruned from the spending program, its value is a placeholder",
+            name
+        );
+    }
+
+    let witness_file = serde_json::to_string_pretty(&decoded.to_json(&declared)?)?;
+    match output_path {
+        Some(path) => {
+            fs::write(&path, witness_file)
+                .with_context(|| format!("Failed to write output file: {}", path.display()))?;
+        }
+        None => {
+            println!("{}", witness_file);
+        }
+    }
+
+    if let Some(compare_path) = compare {
+        let content = fs::read_to_string(&compare_path)
+            .with_context(|| format!("Failed to read witness file: {}", compare_path.display()))?;
+        let differences = decoded.compare(&content)?;
+        for difference in &differences {
+            println!("{}", difference);
+        }
+        if !differences.is_empty() {
+            anyhow::bail!("Decoded witness differs from {}", compare_path.display());
+        }
+        println!("Decoded witness matches {}", compare_path.display());
+    }
+    Ok(())
+}
+
+fn handle_witness_template(
+    path: PathBuf,
+    output_path: Option<PathBuf>,
+    preprocessor: PreprocessorArgs,
+) -> Result<()> {
+    let program = load_source(&path, &preprocessor)?;
+    let types = witness::declarations(&program)?.witnesses;
+    let template = serde_json::to_string_pretty(&witness::template(&types))?;
+
+    match output_path {
+        Some(path) => {
+            fs::write(&path, template)
+                .with_context(|| format!("Failed to write output file: {}", path.display()))?;
+            for (name, ty) in &types {
+                println!("{}: {}", name, ty);
+            }
+        }
+        None => {
+            println!("{}", template);
+        }
+    }
+    Ok(())
+}
+
+fn handle_stwo_witness(proof: PathBuf, output_path: Option<PathBuf>) -> Result<()> {
+    let witness = stwo::Proof::from_file(&proof)?.witness()?;
+    // Every value must parse as a witness of its type
+    serde_json::from_value::<WitnessValues>(witness.clone())
+        .with_context(|| "Failed to parse the generated witness")?;
+
+    let witness = serde_json::to_string_pretty(&witness)?;
+    match output_path {
+        Some(path) => {
+            fs::write(&path, witness)
+                .with_context(|| format!("Failed to write output file: {}", path.display()))?;
+        }
+        None => {
+            println!("{}", witness);
+        }
+    }
+    Ok(())
+}
+
+fn handle_stwo_config(
+    proof: Option<PathBuf>,
+    testing: Option<PathBuf>,
+    parameters: StwoParameterArgs,
+    output_path: Option<PathBuf>,
+) -> Result<()> {
+    let proof = match proof {
+        Some(proof) => Some(stwo::Proof::from_file(&proof)?.parameters()?),
+        None => None,
+    };
+    let config = parameters.config(proof)?;
+    let testing = match testing {
+        Some(testing) => Some(stwo::Proof::from_file(&testing)?.parameters()?),
+        None => None,
+    };
+    let header = stwo::config_header(&config, testing.as_ref())?;
+
+    match output_path {
+        Some(path) => {
+            fs::write(&path, header)
+                .with_context(|| format!("Failed to write output file: {}", path.display()))?;
+        }
+        None => {
+            print!("{}", header);
+        }
+    }
+    Ok(())
+}
+
+fn handle_stwo_check(proof: PathBuf, path: PathBuf, preprocessor: PreprocessorArgs) -> Result<()> {
+    let proof = stwo::Proof::from_file(&proof)?;
+    // The constants are known once the verifier is preprocessed with the same defines
+    let mut preprocessor = preprocessor.preprocessor();
+    preprocessor
+        .preprocess_file(&path)
+        .with_context(|| format!("Failed to preprocess source file: {}", path.display()))?;
+    let problems = proof.check_config(|name| preprocessor.integer(name))?;
+    if !problems.is_empty() {
+        anyhow::bail!(
+            "The proof does not match the verifier:\n  {}",
+            problems.join("\n  ")
+        );
+    }
+    println!("The proof matches the verifier");
+    Ok(())
+}
+
+fn handle_security(
+    path: Option<PathBuf>,
+    parameters: SecurityParameterArgs,
+    min_bits: Option<u32>,
+    min_proven_bits: Option<u32>,
+    preprocessor: PreprocessorArgs,
+) -> Result<()> {
+    let mut preprocessor = preprocessor.preprocessor();
+    if let Some(path) = &path {
+        preprocessor
+            .preprocess_file(path)
+            .with_context(|| format!("Failed to preprocess source file: {}", path.display()))?;
+    }
+    let parameters = parameters.parameters(|name| match path {
+        Some(_) => preprocessor.integer(name),
+        None => Ok(None),
+    })?;
+    print!("{}", parameters.report());
+
+    let mut problems = Vec::new();
+    for (name, bits, min_bits) in [
+        ("conjectured", parameters.conjectured().bits(), min_bits),
+        ("proven", parameters.proven().bits(), min_proven_bits),
+    ] {
+        if let Some(min_bits) = min_bits.filter(|&min_bits| bits < f64::from(min_bits)) {
+            problems.push(format!(
+                "The {} security of {:.1} bits is below {} bits",
+                name, bits, min_bits
+            ));
+        }
+    }
+    if !problems.is_empty() {
+        anyhow::bail!("{}", problems.join("\n"));
+    }
+    Ok(())
+}
+
+fn handle_lint(
+    path: PathBuf,
+    witness: Option<PathBuf>,
+    mut preprocessor: PreprocessorArgs,
+) -> Result<()> {
+    preprocessor
+        .defines
+        .retain(|define| define.split('=').next() != Some(testing::TESTING_DEFINE));
+    let program = load_source(&path, &preprocessor)?;
+
+    let witness_names = match witness {
+        Some(witness_path) => {
+            let content = fs::read_to_string(&witness_path).with_context(|| {
+                format!("Failed to read witness file: {}", witness_path.display())
+            })?;
+            let values: serde_json::Map<String, serde_json::Value> =
+                serde_json::from_str(&content).with_context(|| "Failed to parse witness")?;
+            values.keys().cloned().collect()
+        }
+        None => Vec::new(),
+    };
+
+    let lints = lint::lint(&program, &witness_names);
+    for lint in &lints {
+        println!("{}", lint);
+    }
+    if !lints.is_empty() {
+        anyhow::bail!("{} problem(s) found", lints.len());
+    }
+    Ok(())
+}
+
+fn handle_fmt(paths: Vec<PathBuf>, check: bool) -> Result<()> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            testing::collect_source_files(&path, &mut files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    let mut unformatted = 0;
+    for file in &files {
+        let source = fs::read_to_string(file)
+            .with_context(|| format!("Failed to read source file: {}", file.display()))?;
+        let formatted = formatter::format_source(&source)
+            .with_context(|| format!("Failed to format source file: {}", file.display()))?;
+        if formatted == source {
+            continue;
+        }
+        unformatted += 1;
+        if check {
+            let line = source
+                .lines()
+                .zip(formatted.lines())
+                .position(|(a, b)| a != b)
+                .unwrap_or_else(|| source.lines().count().min(formatted.lines().count()));
+            println!("Diff in {} at line {}", file.display(), line + 1);
+        } else {
+            fs::write(file, formatted)
+                .with_context(|| format!("Failed to write source file: {}", file.display()))?;
+            println!("Formatted {}", file.display());
+        }
+    }
+    if check && unformatted > 0 {
+        anyhow::bail!("{} file(s) are not formatted", unformatted);
+    }
+    Ok(())
+}
+
 fn main() {
     let cli = Cli::parse();
 
@@ -255,17 +1627,127 @@ fn main() {
             witness,
             output_path,
             output_format,
-        } => handle_build(path, witness, output_path, output_format),
+            json,
+            witness_output,
+            env,
+            preprocessor,
+        } => handle_build(
+            path,
+            witness,
+            output_path,
+            output_format,
+            json,
+            witness_output,
+            env,
+            preprocessor,
+        ),
         Commands::Run {
             path,
             witness,
             param,
-        } => handle_run(path, witness, param),
+            json,
+            env,
+            preprocessor,
+        } => handle_run(path, witness, param, json, env, preprocessor),
         Commands::Debug {
             path,
             witness,
             param,
-        } => handle_debug(path, witness, param),
+            debugger,
+            json,
+            env,
+            preprocessor,
+        } => handle_debug(path, witness, param, debugger, json, env, preprocessor),
+        Commands::Profile {
+            path,
+            witness,
+            param,
+            folded,
+            top,
+            preprocessor,
+        } => handle_profile(path, witness, param, folded, top, preprocessor),
+        Commands::Assemble {
+            path,
+            witness,
+            output_path,
+            witness_output_path,
+            output_format,
+        } => handle_assemble(
+            path,
+            witness,
+            output_path,
+            witness_output_path,
+            output_format,
+        ),
+        Commands::Decode {
+            program,
+            witness,
+            simpl,
+            encoding,
+        } => handle_decode(program, witness, simpl, encoding),
+        Commands::TraceDiff {
+            a,
+            b,
+            skip_branches,
+        } => trace::diff(&a, &b, skip_branches),
+        Commands::Preprocess {
+            path,
+            output_path,
+            preprocessor,
+        } => handle_preprocess(path, output_path, preprocessor),
+        Commands::Test {
+            filter,
+            dir,
+            jobs,
+            debug,
+            env_spec,
+            coverage,
+            preprocessor,
+        } => handle_test(filter, dir, jobs, debug, env_spec, coverage, preprocessor),
+        Commands::CheckWitness {
+            path,
+            witness,
+            param,
+            preprocessor,
+        } => handle_check_witness(path, witness, param, preprocessor),
+        Commands::DecodeWitness {
+            path,
+            spending,
+            param,
+            output_path,
+            compare,
+            preprocessor,
+        } => handle_decode_witness(path, spending, param, output_path, compare, preprocessor),
+        Commands::WitnessTemplate {
+            path,
+            output_path,
+            preprocessor,
+        } => handle_witness_template(path, output_path, preprocessor),
+        Commands::StwoWitness { proof, output_path } => handle_stwo_witness(proof, output_path),
+        Commands::StwoConfig {
+            proof,
+            testing,
+            parameters,
+            output_path,
+        } => handle_stwo_config(proof, testing, parameters, output_path),
+        Commands::StwoCheck {
+            proof,
+            path,
+            preprocessor,
+        } => handle_stwo_check(proof, path, preprocessor),
+        Commands::Security {
+            path,
+            parameters,
+            min_bits,
+            min_proven_bits,
+            preprocessor,
+        } => handle_security(path, parameters, min_bits, min_proven_bits, preprocessor),
+        Commands::Lint {
+            path,
+            witness,
+            preprocessor,
+        } => handle_lint(path, witness, preprocessor),
+        Commands::Fmt { paths, check } => handle_fmt(paths, check),
     };
 
     if let Err(err) = result {
@@ -0,0 +1,1089 @@
+//! C-style preprocessor for SimplicityHL sources.
+//!
+//! Supports the subset of the C preprocessor used by the programs in this repository:
+//! `#include`, object-like and function-like `#define` (with `#` and `##` operators),
+//! `#undef`, conditional blocks (`#if`, `#ifdef`, `#ifndef`, `#elif`, `#else`, `#endif`),
+//! `#error` and `#pragma once`. Comments are stripped from the output.
+//!
+//! The built-in `DEFINE_MAP_ARR_ANY`, `DEFINE_FOLD_ARR_ANY` and `DEFINE_ZIP_ARR_ANY` macros
+//! define array helpers of any size, see [`crate::arrays`].
+
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+
+use crate::arrays::{self, ArrayHelper};
+use crate::source_map::{Location, Preprocessed, SourceMap};
+
+/// Maximum depth of nested includes.
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TokenKind {
+    Ident,
+    Number,
+    Str,
+    Punct,
+    Space,
+    Newline,
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    text: String,
+    /// Names of the macros that must not be expanded in this token
+    hide: BTreeSet<String>,
+    /// Original location of the token; tokens produced by a macro
+    /// are located at the macro invocation
+    location: Option<Location>,
+}
+
+impl Token {
+    fn new(kind: TokenKind, text: impl Into<String>) -> Self {
+        Self {
+            kind,
+            text: text.into(),
+            hide: BTreeSet::new(),
+            location: None,
+        }
+    }
+
+    fn is_space(&self) -> bool {
+        matches!(self.kind, TokenKind::Space | TokenKind::Newline)
+    }
+
+    fn is_punct(&self, text: &str) -> bool {
+        self.kind == TokenKind::Punct && self.text == text
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Macro {
+    /// Parameter names of a function-like macro
+    params: Option<Vec<String>>,
+    /// Whether the last parameter is `...`
+    variadic: bool,
+    body: Vec<Token>,
+}
+
+/// State of a conditional block.
+struct Conditional {
+    /// Whether the enclosing block is active
+    parent_active: bool,
+    /// Whether the current branch is active
+    active: bool,
+    /// Whether any branch of the block has been taken
+    taken: bool,
+    /// Whether `#else` has been seen
+    seen_else: bool,
+}
+
+/// C-style preprocessor with include paths and macro definitions.
+pub struct Preprocessor {
+    include_dirs: Vec<PathBuf>,
+    macros: HashMap<String, Macro>,
+    once: HashSet<PathBuf>,
+    /// Contents used instead of the files on disk, keyed by the canonical path
+    overlays: HashMap<PathBuf, String>,
+    /// Array helpers defined by the built-in macros so far, with the arguments
+    /// (types and functions) they were defined for
+    array_helpers: RefCell<HashMap<String, Vec<String>>>,
+}
+
+impl Preprocessor {
+    /// Creates a preprocessor that searches the given directories for included files.
+    pub fn new(include_dirs: Vec<PathBuf>) -> Self {
+        Self {
+            include_dirs,
+            macros: HashMap::new(),
+            once: HashSet::new(),
+            overlays: HashMap::new(),
+            array_helpers: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Appends a directory to the include search path.
+    pub fn add_include_dir(&mut self, dir: PathBuf) {
+        self.include_dirs.push(dir);
+    }
+
+    /// Uses the given content instead of reading a file, e.g. for unsaved editor buffers.
+    pub fn add_overlay(&mut self, path: &Path, content: String) {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.overlays.insert(canonical, content);
+    }
+
+    /// Defines an object-like macro, as `-D NAME=VALUE` does.
+    pub fn define(&mut self, name: &str, value: &str) {
+        let body = trim_spaces(lex(value));
+        self.macros.insert(
+            name.to_string(),
+            Macro {
+                params: None,
+                variadic: false,
+                body,
+            },
+        );
+    }
+
+    /// Defines a macro from a command line argument in the `NAME[=VALUE]` form.
+    /// The value defaults to `1`.
+    pub fn define_arg(&mut self, arg: &str) {
+        match arg.split_once('=') {
+            Some((name, value)) => self.define(name.trim(), value),
+            None => self.define(arg.trim(), "1"),
+        }
+    }
+
+    /// Evaluates an object-like macro as an integer constant expression, as `#if` does.
+    /// Returns `None` if no such macro is defined.
+    pub fn integer(&self, name: &str) -> Result<Option<i128>> {
+        if self.macros.get(name).is_none_or(|mac| mac.params.is_some()) {
+            return Ok(None);
+        }
+        let tokens: Vec<Token> = self
+            .expand(vec![Token::new(TokenKind::Ident, name)])?
+            .into_iter()
+            .filter(|t| !t.is_space())
+            .collect();
+        let mut parser = ExprParser { tokens, index: 0 };
+        let value = parser
+            .parse_or()
+            .with_context(|| format!("`{}` is not an integer", name))?;
+        if parser.index != parser.tokens.len() {
+            anyhow::bail!("`{}` is not an integer", name);
+        }
+        Ok(Some(value))
+    }
+
+    /// Preprocesses a file, returning the resulting source code
+    /// and the original location of each of its lines.
+    pub fn preprocess_file(&mut self, path: &Path) -> Result<Preprocessed> {
+        let mut output = Preprocessed {
+            source: String::new(),
+            source_map: SourceMap::default(),
+        };
+        self.array_helpers.get_mut().clear();
+        self.process_file(path, 0, &mut output)?;
+        Ok(output)
+    }
+
+    fn process_file(&mut self, path: &Path, depth: usize, output: &mut Preprocessed) -> Result<()> {
+        if depth > MAX_INCLUDE_DEPTH {
+            anyhow::bail!("{}: includes nested too deeply", path.display());
+        }
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if self.once.contains(&canonical) {
+            return Ok(());
+        }
+        let content = match self.overlays.get(&canonical) {
+            Some(content) => strip_comments(content),
+            None => strip_comments(
+                &fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read source file: {}", path.display()))?,
+            ),
+        };
+
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        let mut pending = Vec::new();
+        let lines = logical_lines(&content);
+        let file: Arc<Path> = Arc::from(path);
+
+        for (line_number, line) in lines {
+            let location = format!("{}:{}", path.display(), line_number);
+            let active = conditionals.last().is_none_or(|c| c.active);
+            let trimmed = line.trim_start();
+
+            let Some(directive) = trimmed.strip_prefix('#') else {
+                if active {
+                    let token_location = Location {
+                        file: Arc::clone(&file),
+                        line: line_number,
+                    };
+                    for mut token in lex(&line)
+                        .into_iter()
+                        .chain([Token::new(TokenKind::Newline, "\n")])
+                    {
+                        token.location = Some(token_location.clone());
+                        pending.push(token);
+                    }
+                }
+                continue;
+            };
+
+            let directive = directive.trim_start();
+            let (name, rest) = directive
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .map_or((directive, ""), |i| directive.split_at(i));
+            let rest = rest.trim();
+
+            match name {
+                "ifdef" | "ifndef" => {
+                    let defined = self.macros.contains_key(rest);
+                    let condition = defined == (name == "ifdef");
+                    conditionals.push(Conditional {
+                        parent_active: active,
+                        active: active && condition,
+                        taken: condition,
+                        seen_else: false,
+                    });
+                }
+                "if" => {
+                    let condition = active && self.evaluate(rest).context(location.clone())?;
+                    conditionals.push(Conditional {
+                        parent_active: active,
+                        active: condition,
+                        taken: condition,
+                        seen_else: false,
+                    });
+                }
+                "elif" => {
+                    let parent_active = match conditionals.last() {
+                        Some(c) if !c.seen_else => c.parent_active && !c.taken,
+                        _ => anyhow::bail!("{}: unexpected #elif", location),
+                    };
+                    let condition = parent_active && self.evaluate(rest).context(location)?;
+                    let conditional = conditionals.last_mut().expect("checked above");
+                    conditional.active = condition;
+                    conditional.taken |= condition;
+                }
+                "else" => match conditionals.last_mut() {
+                    Some(c) if !c.seen_else => {
+                        c.active = c.parent_active && !c.taken;
+                        c.taken = true;
+                        c.seen_else = true;
+                    }
+                    _ => anyhow::bail!("{}: unexpected #else", location),
+                },
+                "endif" => {
+                    if conditionals.pop().is_none() {
+                        anyhow::bail!("{}: unexpected #endif", location);
+                    }
+                }
+                _ if !active => {}
+                "" => {}
+                "define" => {
+                    self.flush(&mut pending, output).context(location.clone())?;
+                    self.parse_define(rest).context(location)?;
+                }
+                "undef" => {
+                    self.flush(&mut pending, output).context(location)?;
+                    self.macros.remove(rest);
+                }
+                "include" => {
+                    self.flush(&mut pending, output).context(location.clone())?;
+                    let included = self.resolve_include(path, rest).context(location)?;
+                    self.process_file(&included, depth + 1, output)?;
+                }
+                "pragma" => {
+                    if rest == "once" {
+                        self.once.insert(canonical.clone());
+                    }
+                }
+                "error" => anyhow::bail!("{}: #error {}", location, rest),
+                _ => anyhow::bail!("{}: unknown directive #{}", location, name),
+            }
+        }
+
+        if !conditionals.is_empty() {
+            anyhow::bail!("{}: unterminated conditional block", path.display());
+        }
+        self.flush(&mut pending, output)
+            .with_context(|| path.display().to_string())
+    }
+
+    /// Expands the macros in the pending source code and appends it to the output,
+    /// recording the original location of each new line.
+    fn flush(&self, pending: &mut Vec<Token>, output: &mut Preprocessed) -> Result<()> {
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let tokens = self.expand(std::mem::take(pending))?;
+        let mut last_location = None;
+        for token in tokens {
+            if token.location.is_some() {
+                last_location = token.location.clone();
+            }
+            if output.source.is_empty() || output.source.ends_with('\n') {
+                if let Some(location) = &last_location {
+                    output.source_map.push(location.clone());
+                }
+            }
+            output.source.push_str(&token.text);
+        }
+        Ok(())
+    }
+
+    /// Parses the rest of a `#define` directive.
+    fn parse_define(&mut self, rest: &str) -> Result<()> {
+        let tokens = lex(rest);
+        let name = match tokens.first() {
+            Some(token) if token.kind == TokenKind::Ident => token.text.clone(),
+            _ => anyhow::bail!("macro name missing in #define"),
+        };
+
+        // A parenthesis right after the name starts the parameter list
+        let (params, variadic, body_start) = match tokens.get(1) {
+            Some(token) if token.is_punct("(") => {
+                let mut params = Vec::new();
+                let mut variadic = false;
+                let mut index = 2;
+                loop {
+                    match tokens.get(index) {
+                        Some(token) if token.is_space() || token.is_punct(",") => {}
+                        Some(token) if token.is_punct(")") => break,
+                        Some(token) if token.kind == TokenKind::Ident && !variadic => {
+                            params.push(token.text.clone())
+                        }
+                        Some(token) if token.is_punct(".") && !variadic => {
+                            let dots = tokens[index..].iter().take(3);
+                            if dots.filter(|t| t.is_punct(".")).count() != 3 {
+                                anyhow::bail!("invalid parameter list of macro `{}`", name);
+                            }
+                            params.push("__VA_ARGS__".to_string());
+                            variadic = true;
+                            index += 2;
+                        }
+                        _ => anyhow::bail!("invalid parameter list of macro `{}`", name),
+                    }
+                    index += 1;
+                }
+                (Some(params), variadic, index + 1)
+            }
+            _ => (None, false, 1),
+        };
+
+        let body = trim_spaces(tokens[body_start.min(tokens.len())..].to_vec());
+        self.macros.insert(
+            name,
+            Macro {
+                params,
+                variadic,
+                body,
+            },
+        );
+        Ok(())
+    }
+
+    /// Finds the file referenced by an `#include` directive.
+    fn resolve_include(&self, current: &Path, rest: &str) -> Result<PathBuf> {
+        let expanded;
+        let rest = if rest.starts_with('"') || rest.starts_with('<') {
+            rest
+        } else {
+            // Computed include
+            expanded = self
+                .expand(lex(rest))?
+                .iter()
+                .map(|t| t.text.as_str())
+                .collect::<String>();
+            expanded.trim()
+        };
+
+        let (name, quoted) = if let Some(name) = rest.strip_prefix('"') {
+            (name.strip_suffix('"'), true)
+        } else if let Some(name) = rest.strip_prefix('<') {
+            (name.strip_suffix('>'), false)
+        } else {
+            (None, false)
+        };
+        let name = name.with_context(|| format!("invalid #include {}", rest))?;
+
+        let current_dir = current.parent().map(Path::to_path_buf);
+        quoted
+            .then_some(current_dir)
+            .flatten()
+            .into_iter()
+            .chain(self.include_dirs.iter().cloned())
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+            .with_context(|| format!("included file not found: {}", name))
+    }
+
+    /// Evaluates the condition of an `#if` or `#elif` directive.
+    fn evaluate(&self, condition: &str) -> Result<bool> {
+        // Replace `defined NAME` and `defined(NAME)` before expanding the macros
+        let tokens: Vec<Token> = lex(condition)
+            .into_iter()
+            .filter(|t| !t.is_space())
+            .collect();
+        let mut replaced = Vec::with_capacity(tokens.len());
+        let mut index = 0;
+        while index < tokens.len() {
+            if tokens[index].kind == TokenKind::Ident && tokens[index].text == "defined" {
+                let (name, next) = match tokens.get(index + 1) {
+                    Some(token) if token.is_punct("(") => {
+                        match (tokens.get(index + 2), tokens.get(index + 3)) {
+                            (Some(name), Some(close)) if close.is_punct(")") => (name, index + 4),
+                            _ => anyhow::bail!("invalid `defined` in #if"),
+                        }
+                    }
+                    Some(name) => (name, index + 2),
+                    None => anyhow::bail!("invalid `defined` in #if"),
+                };
+                let value = if self.macros.contains_key(&name.text) {
+                    "1"
+                } else {
+                    "0"
+                };
+                replaced.push(Token::new(TokenKind::Number, value));
+                index = next;
+            } else {
+                replaced.push(tokens[index].clone());
+                index += 1;
+            }
+        }
+
+        let tokens: Vec<Token> = self
+            .expand(replaced)?
+            .into_iter()
+            .filter(|t| !t.is_space())
+            .collect();
+        let mut parser = ExprParser { tokens, index: 0 };
+        let value = parser.parse_or()?;
+        if parser.index != parser.tokens.len() {
+            anyhow::bail!("unexpected tokens in #if: {}", condition);
+        }
+        Ok(value != 0)
+    }
+
+    /// Expands the macros in a sequence of tokens.
+    fn expand(&self, tokens: Vec<Token>) -> Result<Vec<Token>> {
+        let mut input: VecDeque<Token> = tokens.into();
+        let mut output = Vec::new();
+
+        while let Some(token) = input.pop_front() {
+            let mac = match self.macros.get(&token.text) {
+                Some(mac)
+                    if token.kind == TokenKind::Ident && !token.hide.contains(&token.text) =>
+                {
+                    mac
+                }
+                None if token.kind == TokenKind::Ident => {
+                    let Some(helper) = ArrayHelper::from_macro(&token.text) else {
+                        output.push(token);
+                        continue;
+                    };
+                    let open = input.iter().position(|t| !t.is_space());
+                    if open.is_none_or(|i| !input[i].is_punct("(")) {
+                        output.push(token);
+                        continue;
+                    }
+                    input.drain(..=open.expect("checked above"));
+
+                    let (args, _) = collect_args(&mut input)
+                        .with_context(|| format!("unterminated invocation of `{}`", token.text))?;
+                    let mut hide = token.hide.clone();
+                    hide.insert(token.text.clone());
+                    for mut defined in self
+                        .define_array_helpers(helper, &token.text, args)?
+                        .into_iter()
+                        .rev()
+                    {
+                        defined.hide.extend(hide.iter().cloned());
+                        defined.location = token.location.clone();
+                        input.push_front(defined);
+                    }
+                    continue;
+                }
+                _ => {
+                    output.push(token);
+                    continue;
+                }
+            };
+
+            let mut hide = token.hide.clone();
+            let replacement = match &mac.params {
+                None => {
+                    hide.insert(token.text.clone());
+                    self.substitute(mac, &[], &hide)?
+                }
+                Some(params) => {
+                    // A function-like macro name without arguments is left as is
+                    let open = input.iter().position(|t| !t.is_space());
+                    if open.is_none_or(|i| !input[i].is_punct("(")) {
+                        output.push(token);
+                        continue;
+                    }
+                    input.drain(..=open.expect("checked above"));
+
+                    let (args, close) = collect_args(&mut input)
+                        .with_context(|| format!("unterminated invocation of `{}`", token.text))?;
+                    let args = match_params(&token.text, params, mac.variadic, args)?;
+                    hide.retain(|name| close.hide.contains(name));
+                    hide.insert(token.text.clone());
+                    self.substitute(mac, &args, &hide)?
+                }
+            };
+
+            for mut replaced in replacement.into_iter().rev() {
+                replaced.location = token.location.clone();
+                input.push_front(replaced);
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Defines the array helpers of a built-in macro that are not defined yet.
+    fn define_array_helpers(
+        &self,
+        helper: ArrayHelper,
+        name: &str,
+        mut args: Vec<Vec<Token>>,
+    ) -> Result<Vec<Token>> {
+        if args.len() != helper.arity() {
+            anyhow::bail!(
+                "macro `{}` expects {} arguments, but {} were given",
+                name,
+                helper.arity(),
+                args.len()
+            );
+        }
+        let size: Vec<Token> = self
+            .expand(args.pop().expect("checked above"))?
+            .into_iter()
+            .filter(|t| !t.is_space())
+            .collect();
+        let mut parser = ExprParser {
+            tokens: size,
+            index: 0,
+        };
+        let size = parser.parse_or()?;
+        if parser.index != parser.tokens.len() || size < 1 {
+            anyhow::bail!("invalid array size in `{}`", name);
+        }
+        let args: Vec<String> = args
+            .into_iter()
+            .map(|arg| {
+                trim_spaces(arg)
+                    .iter()
+                    .map(|t| if t.is_space() { " " } else { t.text.as_str() })
+                    .collect()
+            })
+            .collect();
+
+        let mut source = String::new();
+        let mut defined = self.array_helpers.borrow_mut();
+        for size in arrays::sizes(size as usize) {
+            let helper_name = helper.name(&args, size);
+            match defined.get(&helper_name) {
+                Some(defined_args) if *defined_args == args => continue,
+                Some(defined_args) => anyhow::bail!(
+                    "`{}` defines `{}` for ({}), but it is already defined for ({})",
+                    name,
+                    helper_name,
+                    args.join(", "),
+                    defined_args.join(", ")
+                ),
+                None => {}
+            }
+            source.push_str(&helper.define(&args, size));
+            source.push(' ');
+            defined.insert(helper_name, args.clone());
+        }
+        Ok(lex(&source))
+    }
+
+    /// Substitutes the arguments into the macro body, applying `#` and `##`.
+    fn substitute(
+        &self,
+        mac: &Macro,
+        args: &[Vec<Token>],
+        hide: &BTreeSet<String>,
+    ) -> Result<Vec<Token>> {
+        let params = mac.params.as_deref().unwrap_or_default();
+        let param_index = |token: &Token| {
+            (token.kind == TokenKind::Ident)
+                .then(|| params.iter().position(|p| *p == token.text))
+                .flatten()
+        };
+        let next_non_space =
+            |from: usize| (from..mac.body.len()).find(|&i| !mac.body[i].is_space());
+
+        let mut output: Vec<Token> = Vec::new();
+        let mut index = 0;
+        while index < mac.body.len() {
+            let token = &mac.body[index];
+
+            // Stringification: `# PARAM`
+            if token.is_punct("#") && mac.params.is_some() {
+                if let Some(next) = next_non_space(index + 1) {
+                    if let Some(param) = param_index(&mac.body[next]) {
+                        output.push(stringify(&args[param]));
+                        index = next + 1;
+                        continue;
+                    }
+                }
+            }
+
+            // Token pasting: `LHS ## RHS`
+            if token.is_punct("##") {
+                let next = next_non_space(index + 1).context("`##` at the end of a macro body")?;
+                let rhs = match param_index(&mac.body[next]) {
+                    Some(param) => trim_spaces(args[param].clone()),
+                    None => vec![mac.body[next].clone()],
+                };
+                while output.last().is_some_and(Token::is_space) {
+                    output.pop();
+                }
+                let mut rhs = rhs.into_iter();
+                match (output.pop(), rhs.next()) {
+                    (Some(lhs), Some(first)) => output.extend(lex(&(lhs.text + &first.text))),
+                    (lhs, first) => output.extend(lhs.into_iter().chain(first)),
+                }
+                output.extend(rhs);
+                index = next + 1;
+                continue;
+            }
+
+            if let Some(param) = param_index(token) {
+                let is_pasted =
+                    next_non_space(index + 1).is_some_and(|i| mac.body[i].is_punct("##"));
+                if is_pasted {
+                    output.extend(trim_spaces(args[param].clone()));
+                } else {
+                    output.extend(self.expand(args[param].clone())?);
+                }
+            } else {
+                output.push(token.clone());
+            }
+            index += 1;
+        }
+
+        for token in &mut output {
+            if token.kind == TokenKind::Newline {
+                // Newlines inside macro arguments do not end the line
+                *token = Token::new(TokenKind::Space, " ");
+            }
+            token.hide.extend(hide.iter().cloned());
+        }
+        Ok(output)
+    }
+}
+
+/// Collects the arguments of a function-like macro invocation up to the closing parenthesis.
+fn collect_args(input: &mut VecDeque<Token>) -> Option<(Vec<Vec<Token>>, Token)> {
+    let mut args = vec![Vec::new()];
+    let mut depth = 0;
+    while let Some(token) = input.pop_front() {
+        if token.is_punct("(") {
+            depth += 1;
+        } else if token.is_punct(")") {
+            if depth == 0 {
+                return Some((args, token));
+            }
+            depth -= 1;
+        } else if token.is_punct(",") && depth == 0 {
+            args.push(Vec::new());
+            continue;
+        }
+        args.last_mut().expect("at least one argument").push(token);
+    }
+    None
+}
+
+/// Checks the number of arguments, merging the variadic ones.
+fn match_params(
+    name: &str,
+    params: &[String],
+    variadic: bool,
+    mut args: Vec<Vec<Token>>,
+) -> Result<Vec<Vec<Token>>> {
+    if params.is_empty() && args.len() == 1 && args[0].iter().all(Token::is_space) {
+        return Ok(Vec::new());
+    }
+    if variadic && args.len() >= params.len() {
+        let rest = args.split_off(params.len() - 1);
+        let mut merged = Vec::new();
+        for (i, arg) in rest.into_iter().enumerate() {
+            if i > 0 {
+                merged.push(Token::new(TokenKind::Punct, ","));
+            }
+            merged.extend(arg);
+        }
+        args.push(merged);
+    }
+    if args.len() != params.len() {
+        anyhow::bail!(
+            "macro `{}` expects {} arguments, but {} were given",
+            name,
+            params.len(),
+            args.len()
+        );
+    }
+    Ok(args)
+}
+
+/// Turns the tokens of an argument into a string literal.
+fn stringify(tokens: &[Token]) -> Token {
+    let mut text = String::new();
+    for token in trim_spaces(tokens.to_vec()) {
+        if token.is_space() {
+            if !text.ends_with(' ') {
+                text.push(' ');
+            }
+        } else {
+            text.push_str(&token.text.replace('\\', "\\\\").replace('"', "\\\""));
+        }
+    }
+    Token::new(TokenKind::Str, format!("\"{}\"", text))
+}
+
+/// Removes leading and trailing whitespace tokens.
+fn trim_spaces(mut tokens: Vec<Token>) -> Vec<Token> {
+    while tokens.last().is_some_and(Token::is_space) {
+        tokens.pop();
+    }
+    let start = tokens
+        .iter()
+        .position(|t| !t.is_space())
+        .unwrap_or(tokens.len());
+    tokens.split_off(start)
+}
+
+/// Splits source code into tokens.
+fn lex(source: &str) -> Vec<Token> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        let start = index;
+        let c = chars[index];
+        let kind = if c == '\n' {
+            index += 1;
+            TokenKind::Newline
+        } else if c.is_whitespace() {
+            while index < chars.len() && chars[index].is_whitespace() && chars[index] != '\n' {
+                index += 1;
+            }
+            TokenKind::Space
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while index < chars.len()
+                && (chars[index].is_ascii_alphanumeric() || chars[index] == '_')
+            {
+                index += 1;
+            }
+            TokenKind::Ident
+        } else if c.is_ascii_digit() {
+            while index < chars.len()
+                && (chars[index].is_ascii_alphanumeric() || chars[index] == '_')
+            {
+                index += 1;
+            }
+            TokenKind::Number
+        } else if c == '"' {
+            index += 1;
+            while index < chars.len() && chars[index] != '"' && chars[index] != '\n' {
+                if chars[index] == '\\' {
+                    index += 1;
+                }
+                index += 1;
+            }
+            index = (index + 1).min(chars.len());
+            TokenKind::Str
+        } else if c == '#' && chars.get(index + 1) == Some(&'#') {
+            index += 2;
+            TokenKind::Punct
+        } else {
+            index += 1;
+            TokenKind::Punct
+        };
+        tokens.push(Token::new(
+            kind,
+            chars[start..index].iter().collect::<String>(),
+        ));
+    }
+    tokens
+}
+
+/// Replaces comments with whitespace, keeping the line breaks.
+fn strip_comments(source: &str) -> String {
+    let mut output = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                }
+                output.push(' ');
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        output.push('\n');
+                    }
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+                output.push(' ');
+            }
+            ('"', _) => {
+                output.push(c);
+                while let Some(c) = chars.next() {
+                    output.push(c);
+                    if c == '\\' {
+                        if let Some(escaped) = chars.next() {
+                            output.push(escaped);
+                        }
+                    } else if c == '"' || c == '\n' {
+                        break;
+                    }
+                }
+            }
+            _ => output.push(c),
+        }
+    }
+    output
+}
+
+/// Splits source code into logical lines, joining the lines that end with a backslash.
+/// Returns each logical line with the number of its first physical line.
+fn logical_lines(source: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+    for (index, line) in source.lines().enumerate() {
+        let (number, mut text) = current.take().unwrap_or((index + 1, String::new()));
+        match line.trim_end().strip_suffix('\\') {
+            Some(continued) => {
+                text.push_str(continued);
+                current = Some((number, text));
+            }
+            None => {
+                text.push_str(line);
+                lines.push((number, text));
+            }
+        }
+    }
+    lines.extend(current);
+    lines
+}
+
+/// Recursive descent parser for the integer expressions of `#if`.
+struct ExprParser {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl ExprParser {
+    /// Consumes the next token if it is one of the given operators.
+    /// Two-character operators are lexed as two punctuation tokens.
+    fn eat(&mut self, ops: &[&str]) -> Option<String> {
+        for op in ops {
+            let len = op.len();
+            let text: String = self.tokens[self.index.min(self.tokens.len())..]
+                .iter()
+                .take(len)
+                .map(|t| t.text.as_str())
+                .collect();
+            let all_punct = self.tokens[self.index.min(self.tokens.len())..]
+                .iter()
+                .take(len)
+                .all(|t| t.kind == TokenKind::Punct);
+            if text == *op && all_punct {
+                // Do not confuse `<` with `<=`, `!` with `!=` and so on
+                let next = self.tokens.get(self.index + len).map(|t| t.text.as_str());
+                if len == 1 && matches!(next, Some("=")) && matches!(*op, "<" | ">" | "!") {
+                    continue;
+                }
+                self.index += len;
+                return Some(op.to_string());
+            }
+        }
+        None
+    }
+
+    fn parse_or(&mut self) -> Result<i128> {
+        let mut value = self.parse_and()?;
+        while self.eat(&["||"]).is_some() {
+            let rhs = self.parse_and()?;
+            value = i128::from(value != 0 || rhs != 0);
+        }
+        Ok(value)
+    }
+
+    fn parse_and(&mut self) -> Result<i128> {
+        let mut value = self.parse_comparison()?;
+        while self.eat(&["&&"]).is_some() {
+            let rhs = self.parse_comparison()?;
+            value = i128::from(value != 0 && rhs != 0);
+        }
+        Ok(value)
+    }
+
+    fn parse_comparison(&mut self) -> Result<i128> {
+        let mut value = self.parse_additive()?;
+        while let Some(op) = self.eat(&["==", "!=", "<=", ">=", "<", ">"]) {
+            let rhs = self.parse_additive()?;
+            value = i128::from(match op.as_str() {
+                "==" => value == rhs,
+                "!=" => value != rhs,
+                "<=" => value <= rhs,
+                ">=" => value >= rhs,
+                "<" => value < rhs,
+                _ => value > rhs,
+            });
+        }
+        Ok(value)
+    }
+
+    fn parse_additive(&mut self) -> Result<i128> {
+        let mut value = self.parse_multiplicative()?;
+        while let Some(op) = self.eat(&["+", "-"]) {
+            let rhs = self.parse_multiplicative()?;
+            value = if op == "+" {
+                value.checked_add(rhs)
+            } else {
+                value.checked_sub(rhs)
+            }
+            .context("overflow in #if")?;
+        }
+        Ok(value)
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<i128> {
+        let mut value = self.parse_unary()?;
+        while let Some(op) = self.eat(&["*", "/", "%"]) {
+            let rhs = self.parse_unary()?;
+            if op != "*" && rhs == 0 {
+                anyhow::bail!("division by zero in #if");
+            }
+            value = match op.as_str() {
+                "*" => value.checked_mul(rhs),
+                "/" => value.checked_div(rhs),
+                _ => value.checked_rem(rhs),
+            }
+            .context("overflow in #if")?;
+        }
+        Ok(value)
+    }
+
+    fn parse_unary(&mut self) -> Result<i128> {
+        if self.eat(&["!"]).is_some() {
+            return Ok(i128::from(self.parse_unary()? == 0));
+        }
+        if self.eat(&["-"]).is_some() {
+            return self.parse_unary()?.checked_neg().context("overflow in #if");
+        }
+        if self.eat(&["("]).is_some() {
+            let value = self.parse_or()?;
+            self.eat(&[")"]).context("missing `)` in #if")?;
+            return Ok(value);
+        }
+        let token = self
+            .tokens
+            .get(self.index)
+            .context("unexpected end of #if")?;
+        self.index += 1;
+        match token.kind {
+            TokenKind::Number => parse_integer(&token.text),
+            // Identifiers that are not macros evaluate to zero
+            TokenKind::Ident => Ok(0),
+            _ => anyhow::bail!("unexpected `{}` in #if", token.text),
+        }
+    }
+}
+
+/// Parses an integer literal, ignoring the C suffixes.
+fn parse_integer(text: &str) -> Result<i128> {
+    let lower = text.to_ascii_lowercase();
+    let (digits, radix) = match lower.strip_prefix("0x") {
+        Some(hex) => (hex.trim_end_matches(['u', 'l']), 16),
+        None => match lower.strip_prefix("0b") {
+            Some(bin) => (bin.trim_end_matches(['u', 'l']), 2),
+            None => (lower.trim_end_matches(['u', 'l']), 10),
+        },
+    };
+    i128::from_str_radix(&digits.replace('_', ""), radix)
+        .with_context(|| format!("invalid integer `{}`", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preprocess(source: &str) -> String {
+        // The overlay stands in for a file that does not exist on disk
+        let path = Path::new("test.simf");
+        let mut preprocessor = Preprocessor::new(Vec::new());
+        preprocessor.add_overlay(path, source.to_string());
+        preprocessor.define_arg("TESTING");
+        let output = preprocessor.preprocess_file(path).unwrap();
+        let num_lines = output.source.lines().count();
+        assert!(output.source_map.location(num_lines).is_some());
+        assert!(output.source_map.location(num_lines + 1).is_none());
+        output
+            .source
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn token_pasting() {
+        let source =
+            "#define DEFINE_ID(T, N) \\\nfn id_##N(x: [T; N]) -> [T; N] { x }\nDEFINE_ID(u32, 4)\n";
+        assert_eq!(preprocess(source), "fn id_4(x: [u32; 4]) -> [u32; 4] { x }");
+    }
+
+    #[test]
+    fn nested_expansion() {
+        let source = "#define N 8\n#define HALF(X) X / 2\n#define CALL(F, X) F##_arr_##X\nlet a: u32 = HALF(N);\nCALL(sum, 8)(arr)\n";
+        assert_eq!(preprocess(source), "let a: u32 = 8 / 2; sum_arr_8(arr)");
+    }
+
+    #[test]
+    fn conditionals() {
+        let source = "#ifdef TESTING\na\n#ifndef TESTING\nb\n#else\nc\n#endif\n#else\nd\n#endif\n#if defined(TESTING) && TESTING > 0\ne\n#elif 1\nf\n#endif\n";
+        assert_eq!(preprocess(source), "a c e");
+    }
+
+    #[test]
+    fn overflow_in_conditionals() {
+        let path = Path::new("test.simf");
+        for source in [
+            "#if 0x7fffffffffffffffffffffffffffffff * 2\n#endif\n",
+            "#if -0x7fffffffffffffffffffffffffffffff - 2\n#endif\n",
+        ] {
+            let mut preprocessor = Preprocessor::new(Vec::new());
+            preprocessor.add_overlay(path, source.to_string());
+            let error = preprocessor.preprocess_file(path).unwrap_err();
+            assert!(format!("{:#}", error).contains("overflow in #if"));
+        }
+    }
+
+    #[test]
+    fn comments_and_self_reference() {
+        let source = "/// doc\n#define X X + 1 // comment\n/* block\ncomment */ X\n";
+        assert_eq!(preprocess(source), "X + 1");
+    }
+
+    #[test]
+    fn array_helpers() {
+        let source = "#define N 3\nDEFINE_FOLD_ARR_ANY(u32, u32, sum, N)\nDEFINE_FOLD_ARR_ANY(u32, u32, sum, 2)\n";
+        let output = preprocess(source);
+        assert!(output.starts_with("fn sum_fold_arr_1(arr: [u32; 1], acc: u32) -> u32 {"));
+        assert_eq!(output.matches("fn sum_fold_arr_").count(), 3);
+        assert!(output.contains("= <[u32; 3]>::into(arr);"));
+    }
+
+    #[test]
+    fn conflicting_array_helpers() {
+        let path = Path::new("test.simf");
+        let source = "DEFINE_ZIP_ARR_ANY(pair, u8, u16, 4)\nDEFINE_ZIP_ARR_ANY(pair, u8, u32, 2)\n";
+        let mut preprocessor = Preprocessor::new(Vec::new());
+        preprocessor.add_overlay(path, source.to_string());
+        let error = preprocessor.preprocess_file(path).unwrap_err();
+        assert!(format!("{:#}", error).contains("`pair_zip_arr_1`"));
+    }
+}
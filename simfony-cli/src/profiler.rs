@@ -0,0 +1,202 @@
+//! Profiler that counts jet calls and attributes their cost to source functions.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use simfony::debug::DebugSymbols;
+
+use simplicity::bit_machine::ExecTracker;
+use simplicity::ffi::ffi::UWORD;
+use simplicity::jet::{Elements, Jet};
+use simplicity::Cmr;
+
+use simfony_cli::source_map::Preprocessed;
+
+use crate::tracker::{CallLocator, CallSite, CallStack};
+
+/// Name of the stack frame for code outside of any known function.
+const UNKNOWN_FUNCTION: &str = "<unknown>";
+
+/// Name of the leaf frame that is charged with the jets whose call occurs in several functions,
+/// none of which is on the call stack, e.g. `jet::sha_256_ctx_8_init()`.
+const AMBIGUOUS_FUNCTION: &str = "<ambiguous>";
+
+/// Number of calls and total cost in milliweight units.
+#[derive(Clone, Copy, Default)]
+pub struct Stats {
+    pub calls: u64,
+    pub cost: u64,
+}
+
+impl Stats {
+    fn add(&mut self, cost: u64) {
+        self.calls += 1;
+        self.cost += cost;
+    }
+}
+
+/// Execution tracker that profiles the jet calls.
+///
+/// Simplicity has no notion of function calls, so the call stack is reconstructed
+/// from the tracked calls (see [`CallStack`]). The Bit Machine calls a jet before it reaches
+/// the tracked call that contains it, so each jet is located by its name, as in the debugger.
+/// The cost of a jet that cannot be attributed to a function goes to an `<ambiguous>` leaf
+/// of the current stack instead of a guess.
+pub struct Profiler<'a> {
+    debug_symbols: &'a DebugSymbols,
+    locator: CallLocator<'a>,
+    stack: CallStack,
+    pub jets: HashMap<String, Stats>,
+    /// Cost of the jets called directly by each function
+    pub functions: HashMap<String, Stats>,
+    /// Cost of the jets called by each call stack, joined with `;`
+    pub stacks: HashMap<String, u64>,
+}
+
+impl<'a> Profiler<'a> {
+    pub fn new(debug_symbols: &'a DebugSymbols, program: &'a Preprocessed) -> Self {
+        Self {
+            debug_symbols,
+            locator: CallLocator::new(program),
+            stack: CallStack::default(),
+            jets: HashMap::new(),
+            functions: HashMap::new(),
+            stacks: HashMap::new(),
+        }
+    }
+
+    /// Total cost of all jet calls.
+    pub fn total_cost(&self) -> u64 {
+        self.jets.values().map(|stats| stats.cost).sum()
+    }
+
+    /// Writes the jets and the functions sorted by cost, at most `top` rows each.
+    pub fn write_tables<W: Write>(&self, output: &mut W, top: usize) -> std::io::Result<()> {
+        let total = self.total_cost().max(1) as f64;
+        for (title, stats) in [("Jet", &self.jets), ("Function", &self.functions)] {
+            let mut rows: Vec<(&String, &Stats)> = stats.iter().collect();
+            rows.sort_by(|a, b| b.1.cost.cmp(&a.1.cost).then(a.0.cmp(b.0)));
+
+            writeln!(
+                output,
+                "{:<40} {:>12} {:>16} {:>8}",
+                title, "Calls", "Cost (mWU)", "Cost %"
+            )?;
+            for (name, stats) in rows.iter().take(top) {
+                writeln!(
+                    output,
+                    "{:<40} {:>12} {:>16} {:>7.2}%",
+                    name,
+                    stats.calls,
+                    stats.cost,
+                    100.0 * stats.cost as f64 / total
+                )?;
+            }
+            if rows.len() > top {
+                writeln!(output, "... {} more", rows.len() - top)?;
+            }
+            writeln!(output)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the call stacks in the folded format of flamegraph tools:
+    /// `main;verify;fold_line 1234`.
+    pub fn write_folded<W: Write>(&self, output: &mut W) -> std::io::Result<()> {
+        let mut stacks: Vec<(&String, &u64)> = self.stacks.iter().collect();
+        stacks.sort();
+        for (stack, cost) in stacks {
+            writeln!(output, "{} {}", stack, cost)?;
+        }
+        Ok(())
+    }
+}
+
+impl ExecTracker<Elements> for Profiler<'_> {
+    fn track_left(&mut self, _: simplicity::Ihr) {}
+
+    fn track_right(&mut self, _: simplicity::Ihr) {}
+
+    fn track_jet_call(&mut self, jet: &Elements, _: &[UWORD], _: &[UWORD], _: bool) {
+        let cost = u64::from(u32::from(jet.cost()));
+        let name = jet.to_string();
+        self.jets.entry(name.clone()).or_default().add(cost);
+
+        let function = match self.locator.jet_site(&name, &self.stack) {
+            CallSite::Function { name, .. } => name,
+            CallSite::Ambiguous => AMBIGUOUS_FUNCTION.to_string(),
+            // Jets that are not called by name, such as `verify` of `assert!`
+            CallSite::Unknown => self.stack.current().unwrap_or(UNKNOWN_FUNCTION).to_string(),
+        };
+        self.functions
+            .entry(function.clone())
+            .or_default()
+            .add(cost);
+
+        let mut stack: Vec<&str> = self.stack.functions().collect();
+        match stack.iter().rposition(|frame| *frame == function) {
+            Some(index) => stack.truncate(index + 1),
+            None => stack.push(&function),
+        }
+        *self.stacks.entry(stack.join(";")).or_default() += cost;
+    }
+
+    fn track_dbg_call(&mut self, cmr: &Cmr, _: simplicity::Value) {
+        let debug_symbols = self.debug_symbols;
+        if let Some(tracked_call) = debug_symbols.get(cmr) {
+            self.enter(cmr, tracked_call.text());
+        }
+    }
+}
+
+impl Profiler<'_> {
+    /// Updates the call stack with a tracked call. An ambiguous call leaves the stack as it is.
+    fn enter(&mut self, cmr: &Cmr, text: &str) {
+        if let CallSite::Function { name, .. } = self.locator.site(cmr, text, &self.stack) {
+            self.stack.track(&name, cmr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simfony::{dummy_env, Arguments, CompiledProgram, WitnessValues};
+    use simplicity::BitMachine;
+    use std::path::Path;
+
+    #[test]
+    fn attribute_jets_to_their_functions() {
+        // `multiply_32` runs before any tracked call is reached, and `add_32` runs
+        // before its own tracked call, while the last one was in `square`
+        let source = "\
+fn square(x: u32) -> u64 {
+    jet::multiply_32(x, x)
+}
+
+fn main() {
+    let a: u64 = square(2);
+    let (_, b): (bool, u32) = jet::add_32(1, 2);
+    assert!(jet::eq_64(a, 4));
+    assert!(jet::eq_32(b, 3));
+}
+";
+        let program = Preprocessed::single_file(Path::new("src/main.simf"), source);
+        let compiled = CompiledProgram::new(source, Arguments::default(), true).unwrap();
+        let satisfied = compiled.satisfy(WitnessValues::default()).unwrap();
+        let node = satisfied.redeem();
+        let mut profiler = Profiler::new(satisfied.debug_symbols(), &program);
+        BitMachine::for_program(node)
+            .unwrap()
+            .exec_with_tracker(node, &dummy_env::dummy(), &mut profiler)
+            .unwrap();
+
+        let calls = |function: &str| profiler.functions.get(function).map(|stats| stats.calls);
+        assert_eq!(calls("square"), Some(1));
+        // `add_32`, `eq_64`, `eq_32` and the `verify` of both assertions
+        assert_eq!(calls("main"), Some(5));
+        assert_eq!(calls(UNKNOWN_FUNCTION), None);
+        assert_eq!(profiler.jets["multiply_32"].calls, 1);
+        assert_eq!(profiler.stacks.values().sum::<u64>(), profiler.total_cost());
+    }
+}
@@ -0,0 +1,88 @@
+//! Machine-readable summary of a compiled program for `--json` output.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Serialize;
+
+use simplicity::jet::Elements;
+use simplicity::node::CommitNode;
+use simplicity::RedeemNode;
+
+#[derive(Serialize)]
+pub struct Report {
+    /// Commitment Merkle root in hex
+    pub cmr: String,
+    /// Encoded program in base64
+    pub program: String,
+    pub program_size: usize,
+    /// Encoded witness in base64, if the program is satisfied
+    pub witness: Option<String>,
+    pub witness_size: Option<usize>,
+    pub bounds: Option<Bounds>,
+    /// Size of the annex padding required to cover the cost
+    pub padding_size: Option<usize>,
+    /// Whether the cost is below the consensus limit
+    pub consensus_valid: Option<bool>,
+    /// Output of the program execution
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Bounds {
+    /// Cost in milliweight units
+    pub cost: u32,
+    pub extra_cells: usize,
+    pub extra_frames: usize,
+}
+
+impl Report {
+    /// Summarizes an unsatisfied program.
+    pub fn commit(node: &CommitNode<Elements>) -> Self {
+        let program_bytes = node.encode_to_vec();
+        Self {
+            cmr: node.cmr().to_string(),
+            program: STANDARD.encode(&program_bytes),
+            program_size: program_bytes.len(),
+            witness: None,
+            witness_size: None,
+            bounds: None,
+            padding_size: None,
+            consensus_valid: None,
+            result: None,
+        }
+    }
+
+    /// Summarizes a satisfied program.
+    pub fn redeem(node: &RedeemNode<Elements>) -> Self {
+        let (program_bytes, witness_bytes) = node.encode_to_vec();
+        let bounds = node.bounds();
+        let padding_size = bounds
+            .cost
+            .get_padding(&vec![witness_bytes.clone(), program_bytes.clone()])
+            .unwrap_or_default()
+            .len();
+
+        Self {
+            cmr: node.cmr().to_string(),
+            program: STANDARD.encode(&program_bytes),
+            program_size: program_bytes.len(),
+            witness: Some(STANDARD.encode(&witness_bytes)),
+            witness_size: Some(witness_bytes.len()),
+            bounds: Some(Bounds {
+                cost: u32::from(bounds.cost),
+                extra_cells: bounds.extra_cells,
+                extra_frames: bounds.extra_frames,
+            }),
+            padding_size: Some(padding_size),
+            consensus_valid: Some(bounds.cost.is_consensus_valid()),
+            result: None,
+        }
+    }
+
+    /// Prints the report as a JSON object.
+    pub fn print(&self) -> anyhow::Result<()> {
+        println!("{}", serde_json::to_string_pretty(self)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+//! Bits of security of a STARK verifier, given the parameters of its FRI protocol.
+//!
+//! Each bound is the smaller of a query term, which the prover can only beat by guessing
+//! the queries, and a field term, which it can only beat by guessing the challenges:
+//!
+//! - The conjectured bound follows the ethSTARK conjecture: every query gives
+//!   `log_blowup_factor` bits, the proof of work adds `pow_bits`, and the field gives
+//!   `log |F| - lde_log_size` bits.
+//! - The proven bound follows the Johnson bound of the proximity gaps paper (BCIKS20)
+//!   with `m = 3`: every query gives `-log(sqrt(rho) * (1 + 1/2m))` bits for the rate
+//!   `rho = 2^-log_blowup_factor`, and the field gives
+//!   `log(2 * rho^(3/2) * |F| / ((m + 1/2)^7 * |D|^2))` bits for the evaluation domain `D`.
+
+use anyhow::{anyhow, Result};
+
+/// Modulus of the secure field QM31 of stwo, the fourth power of the M31 prime.
+pub const QM31_MODULUS: u128 = {
+    let p = (1u128 << 31) - 1;
+    p * p * p * p
+};
+
+/// Multiplicity of the Johnson bound.
+const JOHNSON_M: f64 = 3.0;
+
+/// Parameters of a verifier that determine its soundness.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Parameters {
+    pub trace_log_size: u32,
+    pub log_blowup_factor: u32,
+    pub n_queries: usize,
+    pub pow_bits: u32,
+    /// Size of the field of the challenges
+    pub field_modulus: u128,
+}
+
+/// Bits of security of a bound, as the smaller of its terms.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bound {
+    pub queries: f64,
+    pub field: f64,
+}
+
+impl Bound {
+    /// Bits of security, at least 0.
+    pub fn bits(&self) -> f64 {
+        self.queries.min(self.field).max(0.0)
+    }
+}
+
+impl Parameters {
+    /// Log size of the evaluation domain.
+    pub fn lde_log_size(&self) -> u32 {
+        self.trace_log_size + self.log_blowup_factor
+    }
+
+    fn field_bits(&self) -> f64 {
+        (self.field_modulus as f64).log2()
+    }
+
+    /// Conjectured bits of security.
+    pub fn conjectured(&self) -> Bound {
+        Bound {
+            queries: (self.n_queries as f64) * f64::from(self.log_blowup_factor)
+                + f64::from(self.pow_bits),
+            field: self.field_bits() - f64::from(self.lde_log_size()),
+        }
+    }
+
+    /// Proven bits of security.
+    pub fn proven(&self) -> Bound {
+        let log_blowup = f64::from(self.log_blowup_factor);
+        let per_query = log_blowup / 2.0 - (1.0 + 1.0 / (2.0 * JOHNSON_M)).log2();
+        Bound {
+            queries: (self.n_queries as f64) * per_query + f64::from(self.pow_bits),
+            field: self.field_bits() + 1.0
+                - 1.5 * log_blowup
+                - 7.0 * (JOHNSON_M + 0.5).log2()
+                - 2.0 * f64::from(self.lde_log_size()),
+        }
+    }
+
+    /// Writes the parameters and both bounds.
+    pub fn report(&self) -> String {
+        let bound = |name: &str, bound: Bound| {
+            format!(
+                "{} security: {:.1} bits (queries {:.1}, field {:.1})\n",
+                name,
+                bound.bits(),
+                bound.queries,
+                bound.field
+            )
+        };
+        format!(
+            "Trace log size {}, log blowup factor {}, {} queries, {} bits of proof of work, \
+             {:.1}-bit field\n{}{}",
+            self.trace_log_size,
+            self.log_blowup_factor,
+            self.n_queries,
+            self.pow_bits,
+            self.field_bits(),
+            bound("Conjectured", self.conjectured()),
+            bound("Proven", self.proven())
+        )
+    }
+}
+
+/// Bits of proof of work that `POW_TARGET_64` requires.
+pub fn pow_bits(target: i128) -> Result<u32> {
+    match u64::try_from(target) {
+        Ok(target) if target > 0 => Ok((target - 1).leading_zeros()),
+        _ => Err(anyhow!(
+            "POW_TARGET_64 is {}, which is not a positive 64-bit target",
+            target
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn security_of_verifiers() {
+        let stwo = Parameters {
+            trace_log_size: 9,
+            log_blowup_factor: 4,
+            n_queries: 16,
+            pow_bits: pow_bits(0x07ffffffffffffff).unwrap(),
+            field_modulus: QM31_MODULUS,
+        };
+        assert_eq!(stwo.conjectured().bits(), 69.0);
+        assert_eq!(stwo.proven().bits().floor(), 33.0);
+
+        let stark101 = Parameters {
+            trace_log_size: 10,
+            log_blowup_factor: 3,
+            n_queries: 1,
+            pow_bits: 0,
+            field_modulus: 3221225473,
+        };
+        assert_eq!(stark101.conjectured().bits(), 3.0);
+        assert_eq!(stark101.proven().bits(), 0.0);
+    }
+}
@@ -0,0 +1,270 @@
+//! Mapping from the lines of preprocessed source code back to the original files.
+
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+/// A line in an original source file.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Location {
+    pub file: Arc<Path>,
+    /// 1-based line number
+    pub line: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.line)
+    }
+}
+
+/// Original location of every line of the preprocessed source code.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    lines: Vec<Location>,
+}
+
+/// Preprocessed source code together with its source map.
+#[derive(Clone, Debug)]
+pub struct Preprocessed {
+    pub source: String,
+    pub source_map: SourceMap,
+}
+
+impl SourceMap {
+    /// Records the original location of the next line of the preprocessed source code.
+    pub fn push(&mut self, location: Location) {
+        self.lines.push(location);
+    }
+
+    /// Returns the original location of a 1-based line of the preprocessed source code.
+    pub fn location(&self, line: usize) -> Option<&Location> {
+        line.checked_sub(1).and_then(|index| self.lines.get(index))
+    }
+
+    /// Returns the original files that contributed lines, in order of their first line.
+    pub fn files(&self) -> Vec<&Path> {
+        let mut files: Vec<&Path> = Vec::new();
+        for location in &self.lines {
+            if !files.contains(&&*location.file) {
+                files.push(&location.file);
+            }
+        }
+        files
+    }
+
+    /// Rewrites the line numbers in the gutter of a compiler error
+    /// (`12 | let x: u32 = ...`) as locations in the original files.
+    pub fn rewrite_error(&self, message: &str) -> String {
+        message
+            .lines()
+            .map(|line| {
+                let trimmed = line.trim_start();
+                let digits = trimmed.len()
+                    - trimmed
+                        .trim_start_matches(|c: char| c.is_ascii_digit())
+                        .len();
+                let (number, rest) = trimmed.split_at(digits);
+                match number.parse::<usize>().ok().and_then(|n| self.location(n)) {
+                    Some(location) if rest.starts_with(" |") => format!("{}{}", location, rest),
+                    _ => line.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// A function definition in the preprocessed source code.
+#[derive(Clone, Debug)]
+pub struct FunctionSpan {
+    pub name: String,
+    /// 1-based line of the `fn` keyword
+    pub start: usize,
+    /// 1-based line of the closing brace
+    pub end: usize,
+}
+
+impl Preprocessed {
+    /// Wraps source code that was not preprocessed, mapping every line to the same line of `path`.
+    pub fn single_file(path: &Path, source: &str) -> Self {
+        let file: Arc<Path> = Arc::from(path);
+        let mut source_map = SourceMap::default();
+        for line in 1..=source.lines().count() {
+            source_map.push(Location {
+                file: Arc::clone(&file),
+                line,
+            });
+        }
+        Self {
+            source: source.to_string(),
+            source_map,
+        }
+    }
+
+    /// Returns the 1-based lines of the occurrences of a code snippet, without repetitions.
+    /// Whitespace in the snippet matches any amount of whitespace,
+    /// since the compiler normalizes the snippets of debug symbols.
+    pub fn find_lines(&self, snippet: &str) -> Vec<usize> {
+        let mut lines: Vec<usize> = Vec::new();
+        let mut line = 1;
+        let mut counted = 0;
+        for offset in find_snippets(&self.source, snippet) {
+            line += self.source[counted..offset].matches('\n').count();
+            counted = offset;
+            if lines.last() != Some(&line) {
+                lines.push(line);
+            }
+        }
+        lines
+    }
+
+    /// Returns the 1-based line of a code snippet, or `None` if the snippet is not found
+    /// or occurs on several lines, e.g. the same jet call in two functions.
+    pub fn find_line(&self, snippet: &str) -> Option<usize> {
+        match self.find_lines(snippet)[..] {
+            [line] => Some(line),
+            _ => None,
+        }
+    }
+
+    /// Returns the original location of a code snippet that occurs on a single line.
+    pub fn locate(&self, snippet: &str) -> Option<&Location> {
+        self.find_line(snippet)
+            .and_then(|line| self.source_map.location(line))
+    }
+
+    /// Finds the function definitions by matching the braces of their bodies.
+    /// The preprocessed source code contains no comments, so every brace is significant.
+    pub fn functions(&self) -> Vec<FunctionSpan> {
+        let source = self.source.as_bytes();
+        let is_ident = |c: u8| c.is_ascii_alphanumeric() || c == b'_';
+        let mut functions = Vec::new();
+        let mut line = 1;
+        let mut index = 0;
+        while index < source.len() {
+            let at_keyword = source[index..].starts_with(b"fn")
+                && (index == 0 || !is_ident(source[index - 1]))
+                && source.get(index + 2).is_some_and(u8::is_ascii_whitespace);
+            if !at_keyword {
+                line += usize::from(source[index] == b'\n');
+                index += 1;
+                continue;
+            }
+
+            let start = line;
+            let name_start = index
+                + 2
+                + source[index + 2..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_whitespace())
+                    .count();
+            let name_len = source[name_start..]
+                .iter()
+                .take_while(|&&c| is_ident(c))
+                .count();
+            let name = self.source[name_start..name_start + name_len].to_string();
+
+            let mut depth = 0;
+            index = name_start + name_len;
+            while index < source.len() {
+                match source[index] {
+                    b'\n' => line += 1,
+                    b'{' => depth += 1,
+                    b'}' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+                index += 1;
+            }
+            functions.push(FunctionSpan {
+                name,
+                start,
+                end: line,
+            });
+            index += 1;
+        }
+        functions
+    }
+}
+
+/// Returns the function that contains a 1-based line of the preprocessed source code.
+pub fn function_at(functions: &[FunctionSpan], line: usize) -> Option<&FunctionSpan> {
+    functions
+        .iter()
+        .find(|function| function.start <= line && line <= function.end)
+}
+
+/// Finds the offsets of a code snippet in the source code, letting whitespace match
+/// any amount of whitespace. A snippet that starts with an identifier does not match
+/// in the middle of a longer identifier.
+fn find_snippets<'a>(source: &'a str, snippet: &str) -> impl Iterator<Item = usize> + 'a {
+    let words: Vec<String> = snippet.split_whitespace().map(str::to_string).collect();
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let first = words.first().cloned().unwrap_or_default();
+    let starts_with_ident = first.starts_with(is_ident);
+    let offsets: Vec<usize> = match first.is_empty() {
+        true => Vec::new(),
+        false => source
+            .match_indices(&first)
+            .map(|(offset, _)| offset)
+            .collect(),
+    };
+    offsets.into_iter().filter(move |&offset| {
+        if starts_with_ident && source[..offset].ends_with(is_ident) {
+            return false;
+        }
+        let mut rest = &source[offset..];
+        words.iter().all(|word| {
+            let trimmed = rest.trim_start();
+            match trimmed.strip_prefix(word.as_str()) {
+                Some(remaining) => {
+                    rest = remaining;
+                    true
+                }
+                None => false,
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preprocessed(source: &str) -> Preprocessed {
+        Preprocessed::single_file(Path::new("src/main.simf"), source)
+    }
+
+    #[test]
+    fn rewrite_error_locations() {
+        let program = preprocessed("fn main() {\n    let x: u32 = 1;\n}\n");
+        assert_eq!(
+            program
+                .source_map
+                .rewrite_error("Expected type\n  2 | let x: u32 = 1;\n    |     ^\n  9 | far"),
+            "Expected type\nsrc/main.simf:2 | let x: u32 = 1;\n    |     ^\n  9 | far"
+        );
+    }
+
+    #[test]
+    fn repeated_snippets_are_ambiguous() {
+        let program = preprocessed(
+            "fn a() {\n    jet::sha_256_ctx_8_init()\n}\n\
+             fn b() {\n    jet::sha_256_ctx_8_init()\n}\n\
+             fn c() {\n    f(x); ff(x); f(x)\n}\n",
+        );
+        assert_eq!(program.find_lines("jet::sha_256_ctx_8_init()"), [2, 5]);
+        assert_eq!(program.find_line("jet::sha_256_ctx_8_init()"), None);
+        assert_eq!(program.locate("jet::sha_256_ctx_8_init()"), None);
+        assert_eq!(program.find_line("f(x)"), Some(8));
+        assert_eq!(
+            program.locate("fn  c()").map(ToString::to_string),
+            Some("src/main.simf:7".to_string())
+        );
+    }
+}
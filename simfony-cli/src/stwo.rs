@@ -0,0 +1,680 @@
+//! Stwo proofs, as serialized to JSON by the prover, and the witness and configuration
+//! of the stwo verifier.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+/// M31 field element
+type M31 = u32;
+/// QM31 field element as two CM31 elements
+type Qm31 = [[M31; 2]; 2];
+/// Merkle hash as big-endian bytes
+type Hash = [u8; 32];
+
+/// Index of the trace tree among the commitment trees
+const TRACE_TREE: usize = 1;
+/// Index of the composition polynomial tree among the commitment trees
+const COMPOSITION_TREE: usize = 2;
+/// Maximum length of a Merkle proof, the bound of `MerkleProof32`
+const MAX_MERKLE_PROOF: usize = 32;
+
+/// A stwo proof.
+#[derive(Deserialize)]
+pub struct Proof {
+    pub config: ProofConfig,
+    /// Merkle roots of the preprocessed, trace and composition polynomial trees
+    pub commitments: Vec<Hash>,
+    /// Out-of-domain samples per tree, column and mask offset
+    pub sampled_values: Vec<Vec<Vec<Qm31>>>,
+    /// Merkle decommitments per tree, with the proofs of all queries one after another
+    pub decommitments: Vec<MerkleDecommitment>,
+    /// Queried values per tree, with the values of all queries one after another
+    pub queried_values: Vec<Vec<M31>>,
+    pub proof_of_work: u64,
+    pub fri_proof: FriProof,
+}
+
+#[derive(Deserialize)]
+pub struct ProofConfig {
+    pub pow_bits: u32,
+    pub fri_config: FriConfig,
+}
+
+#[derive(Deserialize)]
+pub struct FriConfig {
+    pub log_blowup_factor: u32,
+    pub log_last_layer_degree_bound: u32,
+    pub n_queries: usize,
+}
+
+#[derive(Deserialize)]
+pub struct MerkleDecommitment {
+    pub hash_witness: Vec<Hash>,
+    pub column_witness: Vec<M31>,
+}
+
+#[derive(Deserialize)]
+pub struct FriProof {
+    pub first_layer: FriLayer,
+    pub inner_layers: Vec<FriLayer>,
+    pub last_layer_poly: LinePoly,
+}
+
+#[derive(Deserialize)]
+pub struct FriLayer {
+    /// Sibling values of the queries, one per query
+    pub fri_witness: Vec<Qm31>,
+    pub decommitment: MerkleDecommitment,
+    pub commitment: Hash,
+}
+
+#[derive(Deserialize)]
+pub struct LinePoly {
+    pub coeffs: Vec<Qm31>,
+    pub log_size: u32,
+}
+
+/// Parameters of the verifier that `config.simf` defines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub trace_log_size: u32,
+    pub log_blowup_factor: u32,
+    pub log_last_layer_degree_bound: u32,
+    pub n_queries: usize,
+    pub pow_bits: u32,
+    /// Number of trace columns of the AIR
+    pub num_columns: usize,
+}
+
+impl Config {
+    /// Log size of the evaluation domain.
+    pub fn lde_log_size(&self) -> u32 {
+        self.trace_log_size + self.log_blowup_factor
+    }
+
+    /// Number of inner FRI layers, which fold the trace polynomials down to the last layer.
+    pub fn n_layers(&self) -> Result<usize> {
+        self.trace_log_size
+            .checked_sub(self.log_last_layer_degree_bound + 1)
+            .map(|n_layers| n_layers as usize)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Trace log size {} leaves no FRI layers above the last layer degree bound {}",
+                    self.trace_log_size,
+                    self.log_last_layer_degree_bound
+                )
+            })
+    }
+
+    /// Exclusive bound of the first 64 bits of the proof of work hash.
+    pub fn pow_target(&self) -> u64 {
+        u64::MAX.checked_shr(self.pow_bits).unwrap_or(0)
+    }
+
+    /// Writes the defines of the parameters, indented by `indent`.
+    fn defines(&self, indent: &str) -> Result<String> {
+        let n_layers = self.n_layers()?;
+        if !(1..=MAX_FRI_QUERIES).contains(&self.n_queries) {
+            return Err(anyhow!(
+                "The verifier draws 1 to {} FRI queries, got {}",
+                MAX_FRI_QUERIES,
+                self.n_queries
+            ));
+        }
+        if self.pow_bits >= u64::BITS {
+            return Err(anyhow!(
+                "The verifier supports fewer than 64 bits of proof of work, got {}",
+                self.pow_bits
+            ));
+        }
+        let lines = [
+            format!("#define TRACE_LOG_SIZE {}", self.trace_log_size),
+            String::new(),
+            "/// Log size of the extended evaluation domain.".to_string(),
+            "/// trace log size + log blowup factor".to_string(),
+            format!("#define LDE_LOG_SIZE {}", self.lde_log_size()),
+            String::new(),
+            "/// Number of FRI queries.".to_string(),
+            format!("#define NUM_FRI_QUERIES {}", self.n_queries),
+            String::new(),
+            "/// Number of FRI inner layers (depends on the trace size).".to_string(),
+            format!("#define NUM_FRI_LAYERS {}", n_layers),
+            String::new(),
+            "/// The target for the proof of work.".to_string(),
+            format!(
+                "#define POW_TARGET_64 {:#018x} // {} bits",
+                self.pow_target(),
+                self.pow_bits
+            ),
+        ];
+        Ok(lines
+            .iter()
+            .map(|line| match line.is_empty() {
+                true => "\n".to_string(),
+                false => format!("{}{}\n", indent, line),
+            })
+            .collect())
+    }
+}
+
+/// Largest number of FRI queries that `fri_generate_queries` of the verifier draws.
+const MAX_FRI_QUERIES: usize = 32;
+
+/// Writes `config.simf` of the verifier, with the parameters of the `TESTING` build if given.
+pub fn config_header(config: &Config, testing: Option<&Config>) -> Result<String> {
+    if let Some(testing) = testing.filter(|testing| testing.num_columns != config.num_columns) {
+        return Err(anyhow!(
+            "The AIR has {} columns, but {} in the TESTING build",
+            config.num_columns,
+            testing.num_columns
+        ));
+    }
+
+    let mut header = String::from(
+        "// SPDX-FileCopyrightText: 2025 StarkWare Industries Ltd.
+//
+// SPDX-License-Identifier: MIT
+
+// Generated by `simfony stwo-config`.
+
+#ifndef CONFIG_SIMF
+#define CONFIG_SIMF
+
+/// Maximum supported offset for an AIR column with transition constraints.
+/// Upper bound not included, i.e. offset is in range [0, MAX_COLUMN_OFFSET).
+#define MAX_COLUMN_OFFSET 1
+
+/// Number of columns in the AIR (depends on the particular choice).
+",
+    );
+    header.push_str(&format!("#define NUM_COLUMNS {}\n\n", config.num_columns));
+    match testing {
+        Some(testing) => {
+            header.push_str("#ifdef TESTING\n");
+            header.push_str(&testing.defines("    ")?);
+            header.push_str("\n#else\n");
+            header.push_str(&config.defines("    ")?);
+            header.push_str("\n#endif // TESTING\n");
+        }
+        None => header.push_str(&config.defines("")?),
+    }
+    header.push_str("\n#endif\n");
+    Ok(header)
+}
+
+impl Proof {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read proof file: {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse proof file: {}", path.display()))
+    }
+
+    /// Creates the witness file of the verifier: `COMMITMENTS`, `DECOMMITMENTS`, `OODS_EVALS`,
+    /// `FRI_COMMITMENTS`, `FRI_DECOMMITMENTS` and `POW_NONCE`.
+    ///
+    /// Every type is spelled out, with arrays sized by the proof, so that the witness
+    /// does not depend on the aliases of the verifier.
+    pub fn witness(&self) -> Result<serde_json::Value> {
+        let n_queries = self.config.fri_config.n_queries;
+        let [preprocessed_root, trace_root, composition_root] = self.commitments.as_slice() else {
+            return Err(anyhow!(
+                "Expected 3 commitments, got {}",
+                self.commitments.len()
+            ));
+        };
+        let commitments = format!(
+            "({}, {}, {})",
+            u256(preprocessed_root),
+            u256(trace_root),
+            u256(composition_root)
+        );
+
+        // Out-of-domain samples of every trace column at every mask offset,
+        // and of every partition of the composition polynomial at one point
+        let trace_oods = self.tree(&self.sampled_values, TRACE_TREE)?;
+        let composition_oods = self.tree(&self.sampled_values, COMPOSITION_TREE)?;
+        // The queried values have no mask offsets, so the verifier supports only one
+        let col_offsets = 1;
+        if let Some(column) = trace_oods.iter().find(|column| column.len() != col_offsets) {
+            return Err(anyhow!(
+                "Expected one mask offset per trace column, got {}",
+                column.len()
+            ));
+        }
+        let cols = trace_oods.len();
+        let cp_parts = composition_oods.len();
+        let composition_oods = composition_oods
+            .iter()
+            .map(|part| match part.as_slice() {
+                [value] => Ok(qm31(value)),
+                _ => Err(anyhow!(
+                    "Expected one sample per composition partition, got {}",
+                    part.len()
+                )),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let trace_oods = trace_oods
+            .iter()
+            .map(|column| array(column.iter().map(qm31)))
+            .collect::<Vec<_>>();
+        let oods_evals = format!("({}, {})", array(trace_oods), array(composition_oods));
+
+        let trace_values = chunks(
+            self.tree(&self.queried_values, TRACE_TREE)?,
+            n_queries,
+            "trace queried values",
+        )?;
+        let composition_values = chunks(
+            self.tree(&self.queried_values, COMPOSITION_TREE)?,
+            n_queries,
+            "composition queried values",
+        )?;
+        let trace_proofs = chunks(
+            &self.tree(&self.decommitments, TRACE_TREE)?.hash_witness,
+            n_queries,
+            "trace Merkle proofs",
+        )?;
+        let composition_proofs = chunks(
+            &self
+                .tree(&self.decommitments, COMPOSITION_TREE)?
+                .hash_witness,
+            n_queries,
+            "composition Merkle proofs",
+        )?;
+        if let Some(values) = trace_values.iter().find(|values| values.len() != cols) {
+            return Err(anyhow!(
+                "Expected {} trace values per query, got {}",
+                cols,
+                values.len()
+            ));
+        }
+        let decommitments = (0..n_queries)
+            .map(|query| {
+                let trace_evals = array(trace_values[query].iter().map(|value| array([value])));
+                let composition_evals = array(composition_values[query].iter());
+                Ok(format!(
+                    "(({}, {}), ({}, {}))",
+                    trace_evals,
+                    merkle_proof(trace_proofs[query])?,
+                    composition_evals,
+                    merkle_proof(composition_proofs[query])?
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let fri = &self.fri_proof;
+        let n_layers = fri.inner_layers.len();
+        let [last_layer] = fri.last_layer_poly.coeffs.as_slice() else {
+            return Err(anyhow!(
+                "Expected one coefficient of the last FRI layer, got {}",
+                fri.last_layer_poly.coeffs.len()
+            ));
+        };
+        let fri_commitments = format!(
+            "({}, {}, {})",
+            u256(&fri.first_layer.commitment),
+            array(fri.inner_layers.iter().map(|layer| u256(&layer.commitment))),
+            qm31(last_layer)
+        );
+        let inner_layers = fri
+            .inner_layers
+            .iter()
+            .map(|layer| fri_layer(layer, n_queries))
+            .collect::<Result<Vec<_>>>()?;
+        let fri_decommitments = format!(
+            "({}, {})",
+            fri_layer(&fri.first_layer, n_queries)?,
+            array(inner_layers)
+        );
+
+        let types = WitnessTypes {
+            cols,
+            col_offsets,
+            cp_parts,
+            n_queries,
+            n_layers,
+        };
+        let entry = |value: String, ty: String| serde_json::json!({ "value": value, "type": ty });
+        Ok(serde_json::json!({
+            "COMMITMENTS": entry(commitments, "(u256, u256, u256)".to_string()),
+            "DECOMMITMENTS": entry(array(decommitments), types.decommitments()),
+            "OODS_EVALS": entry(oods_evals, types.oods_evals()),
+            "FRI_COMMITMENTS": entry(fri_commitments, types.fri_commitments()),
+            "FRI_DECOMMITMENTS": entry(fri_decommitments, types.fri_decommitments()),
+            "POW_NONCE": entry(self.proof_of_work.to_string(), "u64".to_string()),
+        }))
+    }
+
+    /// Returns the parameters of the verifier for this proof.
+    pub fn parameters(&self) -> Result<Config> {
+        let fri_config = &self.config.fri_config;
+        // The trace is committed on the evaluation domain, one hash per level of the tree
+        let trace_proofs = chunks(
+            &self.tree(&self.decommitments, TRACE_TREE)?.hash_witness,
+            fri_config.n_queries,
+            "trace Merkle proofs",
+        )?;
+        let lde_log_size = trace_proofs[0].len() as u32;
+        let trace_log_size = lde_log_size
+            .checked_sub(fri_config.log_blowup_factor)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Trace Merkle proofs of {} hashes are shorter than the log blowup factor {}",
+                    lde_log_size,
+                    fri_config.log_blowup_factor
+                )
+            })?;
+        Ok(Config {
+            trace_log_size,
+            log_blowup_factor: fri_config.log_blowup_factor,
+            log_last_layer_degree_bound: fri_config.log_last_layer_degree_bound,
+            n_queries: fri_config.n_queries,
+            pow_bits: self.config.pow_bits,
+            num_columns: self.tree(&self.sampled_values, TRACE_TREE)?.len(),
+        })
+    }
+
+    /// Compares the proof with the constants of the verifier in `config.simf`, which `define`
+    /// evaluates by name, explaining every mismatch.
+    pub fn check_config(
+        &self,
+        define: impl Fn(&str) -> Result<Option<i128>>,
+    ) -> Result<Vec<String>> {
+        let config = self.parameters()?;
+        let lde_log_size = config.lde_log_size() as usize;
+        let n_layers = self.fri_proof.inner_layers.len();
+
+        let mut problems = Vec::new();
+        let mut expect = |name: &str, expected: usize, reason: String| -> Result<()> {
+            match define(name)? {
+                None => problems.push(format!("{} is not defined", name)),
+                Some(value) if value != expected as i128 => problems.push(format!(
+                    "{} is {}, but {}, so it must be {}",
+                    name, value, reason, expected
+                )),
+                Some(_) => {}
+            }
+            Ok(())
+        };
+        expect(
+            "LDE_LOG_SIZE",
+            lde_log_size,
+            format!("the trace Merkle proofs have {} hashes", lde_log_size),
+        )?;
+        expect(
+            "TRACE_LOG_SIZE",
+            config.trace_log_size as usize,
+            format!(
+                "the evaluation domain has log size {} and `config.fri_config.log_blowup_factor` is {}",
+                lde_log_size, config.log_blowup_factor
+            ),
+        )?;
+        expect(
+            "NUM_FRI_QUERIES",
+            config.n_queries,
+            format!("`config.fri_config.n_queries` is {}", config.n_queries),
+        )?;
+        expect(
+            "NUM_FRI_LAYERS",
+            n_layers,
+            format!("`fri_proof.inner_layers` has {} layers", n_layers),
+        )?;
+        expect(
+            "NUM_COLUMNS",
+            config.num_columns,
+            format!("the proof samples {} trace columns", config.num_columns),
+        )?;
+
+        let (pow_bits, target) = (config.pow_bits, config.pow_target());
+        match define("POW_TARGET_64")? {
+            None => problems.push("POW_TARGET_64 is not defined".to_string()),
+            Some(value) => match u64::try_from(value) {
+                Ok(value) if value > 0 && (value - 1).leading_zeros() == pow_bits => {}
+                Ok(value) if value > 0 => problems.push(format!(
+                    "POW_TARGET_64 is {:#018x}, which requires {} bits of proof of work, \
+                     but `config.pow_bits` is {}, so it must be {:#018x}",
+                    value,
+                    (value - 1).leading_zeros(),
+                    pow_bits,
+                    target
+                )),
+                _ => problems.push(format!(
+                    "POW_TARGET_64 is {}, which is not a positive 64-bit target, \
+                     but `config.pow_bits` is {}, so it must be {:#018x}",
+                    value, pow_bits, target
+                )),
+            },
+        }
+        Ok(problems)
+    }
+
+    /// Returns the entry of a tree in a per-tree list of the proof.
+    fn tree<'a, T>(&self, trees: &'a [T], tree: usize) -> Result<&'a T> {
+        trees
+            .get(tree)
+            .ok_or_else(|| anyhow!("Expected at least {} trees, got {}", tree + 1, trees.len()))
+    }
+}
+
+/// Sizes of the witness types of the verifier.
+struct WitnessTypes {
+    cols: usize,
+    col_offsets: usize,
+    cp_parts: usize,
+    n_queries: usize,
+    n_layers: usize,
+}
+
+impl WitnessTypes {
+    const QM31: &'static str = "((u32, u32), (u32, u32))";
+    const MERKLE_PROOF: &'static str = "List<u256, 32>";
+
+    fn decommitments(&self) -> String {
+        let trace = format!(
+            "([[u32; {}]; {}], {})",
+            self.col_offsets,
+            self.cols,
+            Self::MERKLE_PROOF
+        );
+        let composition = format!("([u32; {}], {})", self.cp_parts, Self::MERKLE_PROOF);
+        format!("[({}, {}); {}]", trace, composition, self.n_queries)
+    }
+
+    fn oods_evals(&self) -> String {
+        format!(
+            "([[{qm31}; {}]; {}], [{qm31}; {}])",
+            self.col_offsets,
+            self.cols,
+            self.cp_parts,
+            qm31 = Self::QM31
+        )
+    }
+
+    fn fri_commitments(&self) -> String {
+        format!("(u256, [u256; {}], {})", self.n_layers, Self::QM31)
+    }
+
+    fn fri_decommitments(&self) -> String {
+        let layer = format!(
+            "[({}, {}); {}]",
+            Self::QM31,
+            Self::MERKLE_PROOF,
+            self.n_queries
+        );
+        format!("({}, [{}; {}])", layer, layer, self.n_layers)
+    }
+}
+
+/// Formats the decommitment of a FRI layer: the sibling value and the Merkle proof of each query.
+fn fri_layer(layer: &FriLayer, n_queries: usize) -> Result<String> {
+    if layer.fri_witness.len() != n_queries {
+        return Err(anyhow!(
+            "Expected {} FRI witness values, got {}",
+            n_queries,
+            layer.fri_witness.len()
+        ));
+    }
+    let proofs = chunks(
+        &layer.decommitment.hash_witness,
+        n_queries,
+        "FRI Merkle proofs",
+    )?;
+    let queries = layer
+        .fri_witness
+        .iter()
+        .zip(proofs)
+        .map(|(value, proof)| Ok(format!("({}, {})", qm31(value), merkle_proof(proof)?)))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(array(queries))
+}
+
+/// Splits the concatenated data of all queries into the data of each query.
+fn chunks<'a, T>(items: &'a [T], n_queries: usize, what: &str) -> Result<Vec<&'a [T]>> {
+    if items.is_empty() || n_queries == 0 || !items.len().is_multiple_of(n_queries) {
+        return Err(anyhow!(
+            "Cannot split {} {} between {} queries",
+            items.len(),
+            what,
+            n_queries
+        ));
+    }
+    Ok(items.chunks(items.len() / n_queries).collect())
+}
+
+fn u256(hash: &Hash) -> String {
+    format!("0x{}", hex::encode(hash))
+}
+
+fn qm31(value: &Qm31) -> String {
+    let [[a, b], [c, d]] = value;
+    format!("(({}, {}), ({}, {}))", a, b, c, d)
+}
+
+fn merkle_proof(hashes: &[Hash]) -> Result<String> {
+    if hashes.len() >= MAX_MERKLE_PROOF {
+        return Err(anyhow!(
+            "Merkle proof of {} hashes does not fit in MerkleProof32",
+            hashes.len()
+        ));
+    }
+    let hashes: Vec<String> = hashes.iter().map(u256).collect();
+    Ok(format!("list![{}]", hashes.join(", ")))
+}
+
+fn array<T: ToString>(items: impl IntoIterator<Item = T>) -> String {
+    let items: Vec<String> = items.into_iter().map(|item| item.to_string()).collect();
+    format!("[{}]", items.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn witness_of_test_proof() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../stwo-verifier/tests/data/proof_test.json");
+        let witness = Proof::from_file(&path).unwrap().witness().unwrap();
+
+        assert_eq!(witness["POW_NONCE"]["value"], "185");
+        assert_eq!(
+            witness["FRI_COMMITMENTS"]["type"],
+            "(u256, [u256; 2], ((u32, u32), (u32, u32)))"
+        );
+        assert_eq!(
+            witness["DECOMMITMENTS"]["type"],
+            "[(([[u32; 1]; 4], List<u256, 32>), ([u32; 16], List<u256, 32>)); 1]"
+        );
+        let oods_evals = witness["OODS_EVALS"]["value"].as_str().unwrap();
+        assert!(oods_evals.starts_with("([[((1, 0), (0, 0))], [((990025692, 1389754042), "));
+    }
+
+    #[test]
+    fn check_config_of_test_proof() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../stwo-verifier/tests/data/proof_test.json");
+        let proof = Proof::from_file(&path).unwrap();
+        let testing = HashMap::from([
+            ("LDE_LOG_SIZE", 4),
+            ("TRACE_LOG_SIZE", 3),
+            ("NUM_FRI_QUERIES", 1),
+            ("NUM_FRI_LAYERS", 2),
+            ("POW_TARGET_64", 0x07ffffffffffffff),
+        ]);
+        assert_eq!(
+            proof
+                .check_config(|name| Ok(testing.get(name).copied()))
+                .unwrap(),
+            ["NUM_COLUMNS is not defined"]
+        );
+
+        let release = HashMap::from([
+            ("LDE_LOG_SIZE", 13),
+            ("TRACE_LOG_SIZE", 9),
+            ("NUM_FRI_QUERIES", 1),
+            ("NUM_COLUMNS", 4),
+            ("POW_TARGET_64", 0x003fffffffffffff),
+        ]);
+        assert_eq!(
+            proof
+                .check_config(|name| Ok(release.get(name).copied()))
+                .unwrap(),
+            [
+                "LDE_LOG_SIZE is 13, but the trace Merkle proofs have 4 hashes, so it must be 4",
+                "TRACE_LOG_SIZE is 9, but the evaluation domain has log size 4 and `config.fri_config.log_blowup_factor` is 1, so it must be 3",
+                "NUM_FRI_LAYERS is not defined",
+                "POW_TARGET_64 is 0x003fffffffffffff, which requires 10 bits of proof of work, but `config.pow_bits` is 5, so it must be 0x07ffffffffffffff",
+            ]
+        );
+    }
+
+    #[test]
+    fn reject_empty_decommitments() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../stwo-verifier/tests/data/proof_test.json");
+        let mut proof: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        proof["decommitments"][TRACE_TREE]["hash_witness"] = serde_json::json!([]);
+        let proof: Proof = serde_json::from_value(proof).unwrap();
+
+        let error = proof.parameters().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Cannot split 0 trace Merkle proofs between 1 queries"
+        );
+        assert!(proof.witness().is_err());
+    }
+
+    #[test]
+    fn config_header_of_proofs() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../stwo-verifier");
+        let parameters = |name: &str| {
+            Proof::from_file(&dir.join("tests/data").join(name))
+                .unwrap()
+                .parameters()
+                .unwrap()
+        };
+        let config = parameters("proof.json");
+        let testing = parameters("proof_test.json");
+        assert_eq!(config.lde_log_size(), 13);
+        assert_eq!(config.n_layers().unwrap(), 8);
+        assert_eq!(testing.n_layers().unwrap(), 2);
+
+        let header = config_header(&config, Some(&testing)).unwrap();
+        assert_eq!(
+            header,
+            fs::read_to_string(dir.join("src/config.simf")).unwrap()
+        );
+
+        let pow_bits = Config {
+            pow_bits: 64,
+            ..config
+        };
+        assert!(config_header(&pow_bits, None).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! Script-path spending of Simplicity programs on Elements.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use simplicity::elements::secp256k1_zkp as secp256k1;
+use simplicity::elements::taproot::{ControlBlock, LeafVersion, TaprootBuilder, TaprootSpendInfo};
+use simplicity::elements::Script;
+use simplicity::jet::Elements;
+use simplicity::{Cmr, RedeemNode};
+
+/// Internal key without a known discrete logarithm, from BIP-341,
+/// which makes the key path unspendable
+pub const UNSPENDABLE_INTERNAL_KEY: &str =
+    "50929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0";
+
+/// Taproot leaf version for Simplicity programs
+pub fn simplicity_leaf_version() -> LeafVersion {
+    LeafVersion::from_u8(0xbe).expect("constant leaf version")
+}
+
+/// Parses an x-only internal key in hex.
+pub fn parse_internal_key(internal_key: &str) -> Result<secp256k1::XOnlyPublicKey> {
+    secp256k1::XOnlyPublicKey::from_str(internal_key)
+        .map_err(|e| anyhow!("Invalid internal key: {}", e))
+}
+
+/// Script of the taproot leaf of a program, which is its CMR.
+fn leaf_script(cmr: Cmr) -> Script {
+    Script::from(cmr.as_ref().to_vec())
+}
+
+/// Creates the spending info of a taproot output with the program as its only leaf.
+pub fn spend_info(cmr: Cmr, internal_key: secp256k1::XOnlyPublicKey) -> Result<TaprootSpendInfo> {
+    TaprootBuilder::new()
+        .add_leaf_with_ver(0, leaf_script(cmr), simplicity_leaf_version())
+        .map_err(|e| anyhow!("Failed to add leaf to taproot builder: {}", e))?
+        .finalize(secp256k1::SECP256K1, internal_key)
+        .map_err(|e| anyhow!("Failed to finalize taproot builder: {}", e))
+}
+
+/// Creates the control block of a taproot output with the program as its only leaf.
+pub fn control_block(cmr: Cmr, internal_key: secp256k1::XOnlyPublicKey) -> Result<ControlBlock> {
+    spend_info(cmr, internal_key)?
+        .control_block(&(leaf_script(cmr), simplicity_leaf_version()))
+        .ok_or(anyhow!("Failed to create control block"))
+}
+
+/// Assembles the script witness stack that spends a program through the taproot script path:
+/// witness, program, CMR script, control block and the annex padding if the budget requires it.
+pub fn witness_stack(
+    node: &RedeemNode<Elements>,
+    internal_key: secp256k1::XOnlyPublicKey,
+) -> Result<Vec<Vec<u8>>> {
+    let control_block = control_block(node.cmr(), internal_key)?;
+
+    let bounds = node.bounds();
+    if !bounds.cost.is_consensus_valid() {
+        return Err(anyhow!(
+            "Program cost exceeded the maximum allowed cost, cost = {}",
+            bounds.cost
+        ));
+    }
+
+    let (program_bytes, witness_bytes) = node.encode_to_vec();
+    let mut stack = vec![
+        witness_bytes,
+        program_bytes,
+        leaf_script(node.cmr()).into_bytes(),
+        control_block.serialize(),
+    ];
+    // The cost is proportional to the consumed resources, but the budget depends on the
+    // size of the script witness, which the annex pads
+    if let Some(padding) = bounds.cost.get_padding(&stack) {
+        stack.push(padding);
+    }
+    if !bounds.cost.is_budget_valid(&stack) {
+        return Err(anyhow!("Budget exceeded, cost = {}", bounds.cost));
+    }
+    Ok(stack)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simfony::{dummy_env, Arguments, CompiledProgram, WitnessValues};
+
+    #[test]
+    fn witness_stack_layout() {
+        let source = "fn main() {\n    assert!(jet::eq_32(witness::X, 1));\n}\n";
+        let compiled = CompiledProgram::new(source, Arguments::default(), false).unwrap();
+        let witness: WitnessValues =
+            serde_json::from_str(r#"{"X": {"value": "1", "type": "u32"}}"#).unwrap();
+        let satisfied = compiled.satisfy(witness).unwrap();
+        let node = satisfied.redeem();
+        let internal_key = parse_internal_key(UNSPENDABLE_INTERNAL_KEY).unwrap();
+
+        let stack = witness_stack(node, internal_key).unwrap();
+        assert!(stack.len() == 4 || stack.len() == 5);
+        let (program, witness) = node.encode_to_vec();
+        assert_eq!(stack[0], witness);
+        assert_eq!(stack[1], program);
+        assert_eq!(stack[2], node.cmr().as_ref());
+        assert_eq!(stack[3][0] & 0xfe, 0xbe);
+        assert_eq!(
+            stack[3],
+            control_block(node.cmr(), internal_key).unwrap().serialize()
+        );
+    }
+
+    #[test]
+    fn witness_stack_of_pruned_program() {
+        let source = "fn main() {
+    match witness::B {
+        true => assert!(jet::eq_32(witness::X, 1)),
+        false => assert!(jet::eq_32(witness::X, 2)),
+    };
+}
+";
+        let compiled = CompiledProgram::new(source, Arguments::default(), false).unwrap();
+        let witness: WitnessValues = serde_json::from_str(
+            r#"{"B": {"value": "true", "type": "bool"}, "X": {"value": "1", "type": "u32"}}"#,
+        )
+        .unwrap();
+        let unpruned = compiled.satisfy(witness.clone()).unwrap();
+        let satisfied = compiled
+            .satisfy_with_env(witness, Some(&dummy_env::dummy()))
+            .unwrap();
+        let node = satisfied.redeem();
+        let internal_key = parse_internal_key(UNSPENDABLE_INTERNAL_KEY).unwrap();
+
+        let stack = witness_stack(node, internal_key).unwrap();
+        let (program, _) = node.encode_to_vec();
+        let (unpruned_program, _) = unpruned.redeem().encode_to_vec();
+        assert_eq!(stack[1], program);
+        assert!(program.len() < unpruned_program.len());
+        // Pruning keeps the commitment, so the script and control block stay the same
+        assert_eq!(node.cmr(), unpruned.redeem().cmr());
+        assert_eq!(stack[2], node.cmr().as_ref());
+    }
+}
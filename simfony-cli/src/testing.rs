@@ -0,0 +1,421 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use simfony::parse::{Item, ParseFromStr, Program};
+
+use simfony_cli::source_map::Preprocessed;
+
+use crate::coverage::Coverage;
+use crate::{debug_program, parse_arguments, parse_witness, EnvArgs, PreprocessorArgs};
+
+/// Prefix of the functions that are treated as tests.
+pub const TEST_PREFIX: &str = "test_";
+
+/// Define that enables test-only code in the sources.
+pub const TESTING_DEFINE: &str = "TESTING";
+
+/// A test function together with the program it is compiled from.
+pub struct TestCase {
+    /// Qualified name, e.g. `fri::folding::test_fold`
+    pub name: String,
+    /// Name of the test function
+    pub function: String,
+    /// Preprocessed source of the file that defines the test,
+    /// or the error that prevented its compilation
+    pub source: Result<Preprocessed, String>,
+    /// Witness file located next to the source file
+    pub witness: Option<PathBuf>,
+    /// Arguments file located next to the source file
+    pub param: Option<PathBuf>,
+    /// Environment spec located next to the source file, or the default one
+    pub env_spec: Option<PathBuf>,
+}
+
+/// Counts of the test outcomes.
+pub struct TestSummary {
+    pub passed: usize,
+    pub failed: usize,
+    pub filtered_out: usize,
+}
+
+/// A preprocessed source file and the functions it defines (including the included ones).
+struct SourceFile {
+    module: String,
+    path: PathBuf,
+    source: Result<Preprocessed, String>,
+    functions: HashSet<String>,
+    /// Canonical paths of the files it includes, directly or not
+    includes: HashSet<PathBuf>,
+}
+
+/// Discovers test functions in all `.simf` files of a directory.
+///
+/// Each file is preprocessed with `TESTING` defined and parsed.
+/// Since a file also contains the functions of the files it includes,
+/// a test is not attributed to a file that includes another file with the same test.
+pub fn discover_tests(
+    dir: &Path,
+    preprocessor: &PreprocessorArgs,
+    env_spec: Option<&Path>,
+) -> Result<Vec<TestCase>> {
+    let mut paths = Vec::new();
+    collect_source_files(dir, &mut paths)?;
+    paths.sort();
+
+    let files: Vec<SourceFile> = paths
+        .into_iter()
+        .map(|path| load_source_file(dir, path, preprocessor))
+        .collect();
+
+    let mut tests = Vec::new();
+    for file in &files {
+        let source = match &file.source {
+            Ok(source) => source,
+            Err(error) => {
+                // Only report files that were supposed to contain tests
+                let raw = fs::read_to_string(&file.path).unwrap_or_default();
+                if raw.contains(&format!("fn {}", TEST_PREFIX)) {
+                    tests.push(TestCase {
+                        name: file.module.clone(),
+                        function: String::new(),
+                        source: Err(error.clone()),
+                        witness: None,
+                        param: None,
+                        env_spec: None,
+                    });
+                }
+                continue;
+            }
+        };
+
+        let mut functions: Vec<&String> = file
+            .functions
+            .iter()
+            .filter(|name| name.starts_with(TEST_PREFIX))
+            .filter(|name| !includes_definition(&files, file, name))
+            .collect();
+        functions.sort();
+
+        for function in functions {
+            tests.push(TestCase {
+                name: format!("{}::{}", file.module, function),
+                function: function.clone(),
+                source: Ok(source.clone()),
+                witness: existing_file(file.path.with_extension("wit")),
+                param: existing_file(file.path.with_extension("param")),
+                env_spec: existing_file(file.path.with_extension("env.toml"))
+                    .or_else(|| existing_file(file.path.with_extension("env.json")))
+                    .or_else(|| env_spec.map(Path::to_path_buf)),
+            });
+        }
+    }
+
+    Ok(tests)
+}
+
+/// Runs the tests matching the filter in parallel and prints a summary.
+/// In debug mode, the results of function calls are printed on failure (see [`wrap_calls_in_dbg`]).
+/// If a coverage is given, the branches taken by the tests are added to it.
+pub fn run_tests(
+    tests: Vec<TestCase>,
+    filter: Option<&str>,
+    jobs: usize,
+    debug: bool,
+    coverage: Option<Coverage>,
+) -> (TestSummary, Option<Coverage>) {
+    let total = tests.len();
+    let tests: Vec<TestCase> = tests
+        .into_iter()
+        .filter(|test| filter.is_none_or(|f| test.name.contains(f)))
+        .collect();
+    let filtered_out = total - tests.len();
+
+    println!("\nrunning {} tests", tests.len());
+    let started = Instant::now();
+
+    let next = AtomicUsize::new(0);
+    let failures = Mutex::new(Vec::new());
+    let coverage = coverage.map(Mutex::new);
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(test) = tests.get(index) else {
+                    break;
+                };
+                match run_test(test, debug, coverage.as_ref()) {
+                    Ok(()) => println!("test {} ... \x1b[0;32mok\x1b[0m", test.name),
+                    Err(output) => {
+                        println!("test {} ... \x1b[0;31mFAILED\x1b[0m", test.name);
+                        failures.lock().unwrap().push((index, output));
+                    }
+                }
+            });
+        }
+    });
+
+    let mut failures = failures.into_inner().unwrap();
+    failures.sort_by_key(|(index, _)| *index);
+
+    if !failures.is_empty() {
+        println!("\nfailures:\n");
+        for (index, output) in &failures {
+            println!("---- {} ----\n{}\n", tests[*index].name, output.trim_end());
+        }
+        println!("failures:");
+        for (index, _) in &failures {
+            println!("    {}", tests[*index].name);
+        }
+    }
+
+    let summary = TestSummary {
+        passed: tests.len() - failures.len(),
+        failed: failures.len(),
+        filtered_out,
+    };
+    let result = if summary.failed == 0 {
+        "\x1b[0;32mok\x1b[0m"
+    } else {
+        "\x1b[0;31mFAILED\x1b[0m"
+    };
+    println!(
+        "\ntest result: {}. {} passed; {} failed; {} filtered out; finished in {:.2}s\n",
+        result,
+        summary.passed,
+        summary.failed,
+        summary.filtered_out,
+        started.elapsed().as_secs_f64()
+    );
+    (summary, coverage.map(|c| c.into_inner().unwrap()))
+}
+
+/// Compiles a test function as the program entry point and runs it
+/// the same way as `simfony debug` does.
+/// Returns the captured output on failure.
+fn run_test(
+    test: &TestCase,
+    debug: bool,
+    coverage: Option<&Mutex<Coverage>>,
+) -> Result<(), String> {
+    let source = test.source.as_ref().map_err(Clone::clone)?;
+    let mut renamed = rename_function(&source.source, &test.function, "main");
+    if debug {
+        renamed = wrap_calls_in_dbg(&renamed);
+    }
+    let program = Preprocessed {
+        source: renamed,
+        source_map: source.source_map.clone(),
+    };
+
+    let mut output = Vec::new();
+    let mut test_coverage = coverage.map(|_| Coverage::default());
+    let result = (|| -> Result<()> {
+        let param_content = test.param.as_ref().map(fs::read_to_string).transpose()?;
+        let witness_content = test.witness.as_ref().map(fs::read_to_string).transpose()?;
+        let arguments = parse_arguments(param_content.as_deref())?;
+        let witness = parse_witness(witness_content.as_deref())?;
+        let env = EnvArgs {
+            env_spec: test.env_spec.clone(),
+            ..EnvArgs::default()
+        };
+        debug_program(
+            &program,
+            arguments,
+            witness,
+            &env,
+            None,
+            test_coverage.as_mut(),
+            &mut output,
+        )
+    })();
+    if let (Some(coverage), Some(test_coverage)) = (coverage, test_coverage) {
+        coverage.lock().unwrap().merge(test_coverage);
+    }
+
+    result.map_err(|error| {
+        let mut output = String::from_utf8_lossy(&output).into_owned();
+        output.push_str(&format!("Error: {:#}", error));
+        output
+    })
+}
+
+/// Recursively collects the `.simf` files of a directory.
+pub fn collect_source_files(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_source_files(&path, paths)?;
+        } else if path.extension().is_some_and(|ext| ext == "simf") {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Preprocesses and parses a source file, collecting the names of the defined functions.
+fn load_source_file(dir: &Path, path: PathBuf, preprocessor: &PreprocessorArgs) -> SourceFile {
+    let module = path
+        .strip_prefix(dir)
+        .unwrap_or(&path)
+        .with_extension("")
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join("::");
+
+    let parsed = preprocess(&path, dir, preprocessor).and_then(|source| {
+        let program = Program::parse_from_str(&source.source)
+            .map_err(|e| anyhow::anyhow!(source.source_map.rewrite_error(&e.to_string())))?;
+        let functions = program
+            .items()
+            .iter()
+            .filter_map(|item| match item {
+                Item::Function(function) => Some(function.name().as_inner().to_string()),
+                _ => None,
+            })
+            .collect();
+        Ok((source, functions))
+    });
+
+    match parsed {
+        Ok((source, functions)) => {
+            let own = canonical(&path);
+            let includes = source
+                .source_map
+                .files()
+                .into_iter()
+                .map(canonical)
+                .filter(|file| *file != own)
+                .collect();
+            SourceFile {
+                module,
+                path,
+                source: Ok(source),
+                functions,
+                includes,
+            }
+        }
+        Err(error) => SourceFile {
+            module,
+            path,
+            source: Err(format!("{:#}", error)),
+            functions: HashSet::new(),
+            includes: HashSet::new(),
+        },
+    }
+}
+
+/// Preprocesses a source file, defining `TESTING` and searching the source directory for includes.
+fn preprocess(path: &Path, dir: &Path, args: &PreprocessorArgs) -> Result<Preprocessed> {
+    let mut preprocessor = args.preprocessor();
+    preprocessor.add_include_dir(dir.to_path_buf());
+    preprocessor.define(TESTING_DEFINE, "1");
+    preprocessor.preprocess_file(path)
+}
+
+/// Whether a file includes another file that contains the function,
+/// in which case the function is not defined by the file itself.
+fn includes_definition(files: &[SourceFile], file: &SourceFile, function: &str) -> bool {
+    files.iter().any(|other| {
+        other.path != file.path
+            && other.functions.contains(function)
+            && file.includes.contains(&canonical(&other.path))
+    })
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Renames the definition of a function, leaving its other occurrences intact.
+fn rename_function(source: &str, from: &str, to: &str) -> String {
+    let pattern = format!("fn {}", from);
+    let mut result = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(pos) = rest.find(&pattern) {
+        let end = pos + pattern.len();
+        let is_boundary = |c: Option<char>| c.is_none_or(|c| !c.is_alphanumeric() && c != '_');
+        if is_boundary(rest[..pos].chars().next_back()) && is_boundary(rest[end..].chars().next()) {
+            result.push_str(&rest[..pos]);
+            result.push_str("fn ");
+            result.push_str(to);
+        } else {
+            result.push_str(&rest[..end]);
+        }
+        rest = &rest[end..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Wraps the function calls that initialize a `let` binding in `dbg!`, so that a failing
+/// test prints the results it computed: `let x: T = f(a);` becomes `let x: T = dbg!(f(a));`.
+/// Jet calls, conversions and macros are left as they are. Lines are kept, so the source map
+/// stays valid.
+fn wrap_calls_in_dbg(source: &str) -> String {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut result = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(pos) = rest.find("let ") {
+        let start = pos + "let ".len();
+        let at_keyword = rest[..pos].chars().next_back().is_none_or(|c| !is_ident(c));
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if !at_keyword {
+            continue;
+        }
+        // `let PATTERN: TYPE = ` followed by `NAME(ARGS);`
+        let Some(colon) = rest.find(':').filter(|&i| rest[i + 1..].starts_with(' ')) else {
+            continue;
+        };
+        let Some(equals) = rest[colon..].find('=').map(|i| colon + i + 1) else {
+            continue;
+        };
+        let call = equals + rest[equals..].len() - rest[equals..].trim_start().len();
+        let name_len = rest[call..].chars().take_while(|&c| is_ident(c)).count();
+        let Some(end) = rest[call..].find(';').map(|i| call + i) else {
+            continue;
+        };
+        let is_call =
+            name_len > 0 && rest[call + name_len..].starts_with('(') && rest[..end].ends_with(')');
+        if is_call {
+            result.push_str(&rest[..call]);
+            result.push_str("dbg!(");
+            result.push_str(&rest[call..end]);
+            result.push(')');
+            rest = &rest[end..];
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Returns the path if the file exists.
+fn existing_file(path: PathBuf) -> Option<PathBuf> {
+    path.is_file().then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_calls_of_let_bindings() {
+        let source = "fn main() {\n    let (a, b): (u32, u32) = split(x, 1);\n    \
+                      let c: u32 = jet::add_32(a, b);\n    let d: u32 = <u16>::into(c);\n    \
+                      let outlet: u32 = f(g(d));\n}";
+        assert_eq!(
+            wrap_calls_in_dbg(source),
+            "fn main() {\n    let (a, b): (u32, u32) = dbg!(split(x, 1));\n    \
+             let c: u32 = jet::add_32(a, b);\n    let d: u32 = <u16>::into(c);\n    \
+             let outlet: u32 = dbg!(f(g(d)));\n}"
+        );
+    }
+}
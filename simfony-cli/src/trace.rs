@@ -0,0 +1,277 @@
+//! Structured execution traces in the JSON lines format.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use itertools::{Either, Itertools};
+use serde::{Deserialize, Serialize};
+
+use simfony::debug::DebugSymbols;
+use simfony::value::StructuralValue;
+
+use simplicity::bit_machine::ExecTracker;
+use simplicity::ffi::ffi::UWORD;
+use simplicity::jet::Elements;
+use simplicity::{Cmr, Ihr};
+
+use simfony_cli::source_map::Preprocessed;
+
+use crate::tracker::{parse_args, parse_result, CallLocator};
+
+/// An event of the execution, with the values printed as Simfony values.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TraceEvent {
+    /// Call of a jet
+    Jet {
+        jet: String,
+        args: Vec<String>,
+        result: String,
+    },
+    /// Value of a `dbg!` call
+    Dbg {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        location: Option<String>,
+        text: String,
+        value: String,
+    },
+    /// Branch taken by a case node
+    Branch { ihr: String, right: bool },
+}
+
+impl TraceEvent {
+    /// Compares the events, ignoring the source locations and the node hashes
+    /// that change between versions of a program.
+    fn same_as(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                Self::Dbg { text, value, .. },
+                Self::Dbg {
+                    text: other_text,
+                    value: other_value,
+                    ..
+                },
+            ) => text == other_text && value == other_value,
+            (Self::Branch { right, .. }, Self::Branch { right: other, .. }) => right == other,
+            _ => self == other,
+        }
+    }
+}
+
+/// Tracker that records the execution as JSON lines and forwards it to another tracker.
+pub struct TraceRecorder<'a, W: Write, T: ExecTracker<Elements>> {
+    debug_symbols: &'a DebugSymbols,
+    output: W,
+    inner: &'a mut T,
+    locator: CallLocator<'a>,
+    /// First error of writing to the output
+    error: Option<io::Error>,
+}
+
+impl<'a, W: Write, T: ExecTracker<Elements>> TraceRecorder<'a, W, T> {
+    pub fn new(
+        debug_symbols: &'a DebugSymbols,
+        program: &'a Preprocessed,
+        output: W,
+        inner: &'a mut T,
+    ) -> Self {
+        Self {
+            debug_symbols,
+            output,
+            inner,
+            locator: CallLocator::new(program),
+            error: None,
+        }
+    }
+
+    /// Flushes the output and returns the first error of writing the trace.
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => self.output.flush(),
+        }
+    }
+
+    fn record(&mut self, event: TraceEvent) {
+        if self.error.is_some() {
+            return;
+        }
+        let result = serde_json::to_writer(&mut self.output, &event)
+            .map_err(io::Error::from)
+            .and_then(|_| writeln!(self.output));
+        if let Err(error) = result {
+            self.error = Some(error);
+        }
+    }
+}
+
+impl<'a, W: Write, T: ExecTracker<Elements>> ExecTracker<Elements> for TraceRecorder<'a, W, T> {
+    fn track_left(&mut self, ihr: Ihr) {
+        self.record(TraceEvent::Branch {
+            ihr: ihr.to_string(),
+            right: false,
+        });
+        self.inner.track_left(ihr);
+    }
+
+    fn track_right(&mut self, ihr: Ihr) {
+        self.record(TraceEvent::Branch {
+            ihr: ihr.to_string(),
+            right: true,
+        });
+        self.inner.track_right(ihr);
+    }
+
+    fn track_jet_call(
+        &mut self,
+        jet: &Elements,
+        input_buffer: &[UWORD],
+        output_buffer: &[UWORD],
+        success: bool,
+    ) {
+        let args = match parse_args(jet, input_buffer) {
+            Ok(args) => args.iter().map(ToString::to_string).collect(),
+            Err(error) => vec![format!("<TrackerError: {}>", error)],
+        };
+        let result = match parse_result(jet, output_buffer) {
+            Ok(result) => result.to_string(),
+            Err(error) => format!("<TrackerError: {}>", error),
+        };
+        self.record(TraceEvent::Jet {
+            jet: jet.to_string(),
+            args,
+            result,
+        });
+        self.inner
+            .track_jet_call(jet, input_buffer, output_buffer, success);
+    }
+
+    fn track_dbg_call(&mut self, cmr: &Cmr, value: simplicity::Value) {
+        if let Some(tracked_call) = self.debug_symbols.get(cmr) {
+            if let Some(Either::Right(debug_value)) =
+                tracked_call.map_value(&StructuralValue::from(value.clone()))
+            {
+                let location = self
+                    .locator
+                    .line(cmr, debug_value.text())
+                    .and_then(|line| self.locator.location(line))
+                    .map(ToString::to_string);
+                self.record(TraceEvent::Dbg {
+                    location,
+                    text: debug_value.text().to_string(),
+                    value: debug_value.value().to_string(),
+                });
+            }
+        }
+        self.inner.track_dbg_call(cmr, value);
+    }
+}
+
+/// Reads the events of a trace file.
+fn read_trace(path: &Path) -> Result<Vec<TraceEvent>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read trace: {}", path.display()))?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).with_context(|| {
+                format!(
+                    "Failed to parse trace {} at line {}",
+                    path.display(),
+                    index + 1
+                )
+            })
+        })
+        .collect()
+}
+
+/// Formats an event as one line of the diff report.
+fn describe(event: &TraceEvent) -> String {
+    match event {
+        TraceEvent::Jet { jet, args, result } => {
+            format!("jet::{}({}) = {}", jet, args.iter().join(", "), result)
+        }
+        TraceEvent::Dbg {
+            location,
+            text,
+            value,
+        } => match location {
+            Some(location) => format!("dbg [{}]: {} = {}", location, text, value),
+            None => format!("dbg: {} = {}", text, value),
+        },
+        TraceEvent::Branch { ihr, right } => {
+            let side = if *right { "right" } else { "left" };
+            format!("branch {} at {}", side, ihr)
+        }
+    }
+}
+
+/// Compares two traces and prints the first event where they diverge.
+///
+/// Returns an error if the traces diverge, so that the command exits with a failure.
+pub fn diff(a: &Path, b: &Path, skip_branches: bool) -> Result<()> {
+    let keep = |event: &TraceEvent| !skip_branches || !matches!(event, TraceEvent::Branch { .. });
+    let a_events: Vec<TraceEvent> = read_trace(a)?.into_iter().filter(keep).collect();
+    let b_events: Vec<TraceEvent> = read_trace(b)?.into_iter().filter(keep).collect();
+
+    let common = a_events
+        .iter()
+        .zip(&b_events)
+        .take_while(|(a_event, b_event)| a_event.same_as(b_event))
+        .count();
+    if common == a_events.len() && common == b_events.len() {
+        println!("Traces are identical ({} events)", common);
+        return Ok(());
+    }
+
+    println!("Traces diverge at event {}", common + 1);
+    // The last dbg! value before the divergence tells where in the program it happened
+    let last_dbg = a_events[..common]
+        .iter()
+        .rev()
+        .find(|event| matches!(event, TraceEvent::Dbg { .. }));
+    if let Some(event) = last_dbg {
+        println!("  last common dbg! value: {}", describe(event));
+    }
+    for (path, events) in [(a, &a_events), (b, &b_events)] {
+        match events.get(common) {
+            Some(event) => println!("  {}: {}", path.display(), describe(event)),
+            None => println!("  {}: <end of trace>", path.display()),
+        }
+    }
+    Err(anyhow!("Traces diverge after {} common events", common))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_events_across_versions() {
+        let a: TraceEvent = serde_json::from_str(
+            r#"{"event":"dbg","location":"src/fri.simf:42","text":"x","value":"5"}"#,
+        )
+        .unwrap();
+        let b: TraceEvent =
+            serde_json::from_str(r#"{"event":"dbg","text":"x","value":"5"}"#).unwrap();
+        assert!(a.same_as(&b));
+
+        let left = TraceEvent::Branch {
+            ihr: "00".to_string(),
+            right: false,
+        };
+        let right = TraceEvent::Branch {
+            ihr: "00".to_string(),
+            right: true,
+        };
+        assert!(!left.same_as(&right));
+        assert_eq!(
+            serde_json::to_string(&right).unwrap(),
+            r#"{"event":"branch","ihr":"00","right":true}"#
+        );
+    }
+}
@@ -1,3 +1,7 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::Write;
+
 use itertools::{Either, Itertools};
 
 use simfony::debug::DebugSymbols;
@@ -11,36 +15,248 @@ use simplicity::bit_machine::ExecTracker;
 use simplicity::ffi::ffi::UWORD;
 use simplicity::jet::type_name::TypeName;
 use simplicity::jet::{Elements, Jet};
-use simplicity::{
-    BitIter, BitIterCloseError, Cmr, EarlyEndOfStreamError, Value as SimValue, ValueRef,
-};
+use simplicity::{BitIter, Cmr, EarlyEndOfStreamError, Value as SimValue, ValueRef};
+
+use simfony_cli::source_map::{function_at, FunctionSpan, Location, Preprocessed};
 
-pub struct Tracker<'a> {
+pub struct Tracker<'a, W: Write> {
     pub debug_symbols: &'a DebugSymbols,
+    pub program: &'a Preprocessed,
+    pub output: &'a mut W,
+    locator: CallLocator<'a>,
+}
+
+impl<'a, W: Write> Tracker<'a, W> {
+    pub fn new(
+        debug_symbols: &'a DebugSymbols,
+        program: &'a Preprocessed,
+        output: &'a mut W,
+    ) -> Self {
+        Self {
+            debug_symbols,
+            program,
+            output,
+            locator: CallLocator::new(program),
+        }
+    }
 }
 
+/// Finds tracked calls in the preprocessed source code by their text.
+///
+/// The same text may occur in several places, e.g. a jet call in two functions,
+/// so a call is only located if its text occurs once, or once in a function of the call stack.
+pub struct CallLocator<'a> {
+    program: &'a Preprocessed,
+    functions: Vec<FunctionSpan>,
+    /// Preprocessed lines of the occurrences of the tracked calls
+    lines: HashMap<Cmr, Vec<usize>>,
+}
+
+/// Function of a tracked call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CallSite {
+    /// The text of the call is not found in any function
+    Unknown,
+    /// The call is in a function, at a line if its text occurs once in the function
+    Function { name: String, line: Option<usize> },
+    /// The text of the call occurs in several functions, and the call stack does not tell which
+    Ambiguous,
+}
+
+impl<'a> CallLocator<'a> {
+    pub fn new(program: &'a Preprocessed) -> Self {
+        Self {
+            program,
+            functions: program.functions(),
+            lines: HashMap::new(),
+        }
+    }
+
+    fn lines(&mut self, cmr: &Cmr, text: &str) -> &[usize] {
+        let program = self.program;
+        self.lines
+            .entry(*cmr)
+            .or_insert_with(|| program.find_lines(text))
+    }
+
+    /// Returns the preprocessed line of a tracked call whose text occurs on a single line.
+    pub fn line(&mut self, cmr: &Cmr, text: &str) -> Option<usize> {
+        match self.lines(cmr, text) {
+            [line] => Some(*line),
+            _ => None,
+        }
+    }
+
+    /// Returns the first preprocessed line of a tracked call whose text occurs at all.
+    pub fn first_line(&mut self, cmr: &Cmr, text: &str) -> Option<usize> {
+        self.lines(cmr, text).first().copied()
+    }
+
+    /// Returns the function of a tracked call. If its text occurs in several functions,
+    /// the call is in the innermost function of the stack that contains the text
+    /// and has not reached this call yet.
+    pub fn site(&mut self, cmr: &Cmr, text: &str, stack: &CallStack) -> CallSite {
+        let lines = self.lines(cmr, text).to_vec();
+        self.resolve(&lines, stack, |frame| !frame.reached.contains(cmr))
+    }
+
+    /// Returns the function of a jet call. The Bit Machine reports a jet call before the
+    /// tracked call that contains it, so the jet is located by its name: if it is called in
+    /// several functions, the call is in the innermost function of the stack that calls it.
+    pub fn jet_site(&self, name: &str, stack: &CallStack) -> CallSite {
+        let lines = self.program.find_lines(&format!("jet::{}(", name));
+        self.resolve(&lines, stack, |_| true)
+    }
+
+    /// Returns the function that contains the lines of a call, choosing among several
+    /// by the innermost frame of the stack that may make the call.
+    fn resolve(
+        &self,
+        lines: &[usize],
+        stack: &CallStack,
+        may_call: impl Fn(&Frame) -> bool,
+    ) -> CallSite {
+        let mut candidates: Vec<(&str, Vec<usize>)> = Vec::new();
+        for &line in lines {
+            let Some(function) = function_at(&self.functions, line) else {
+                continue;
+            };
+            match candidates
+                .iter_mut()
+                .find(|(name, _)| *name == function.name)
+            {
+                Some((_, lines)) => lines.push(line),
+                None => candidates.push((&function.name, vec![line])),
+            }
+        }
+
+        let found = match candidates.len() {
+            0 => return CallSite::Unknown,
+            1 => candidates.pop(),
+            _ => stack.frames.iter().rev().find_map(|frame| {
+                let index = candidates
+                    .iter()
+                    .position(|(name, _)| *name == frame.function)
+                    .filter(|_| may_call(frame))?;
+                Some(candidates.swap_remove(index))
+            }),
+        };
+        match found {
+            Some((name, lines)) => CallSite::Function {
+                name: name.to_string(),
+                line: match lines[..] {
+                    [line] => Some(line),
+                    _ => None,
+                },
+            },
+            None => CallSite::Ambiguous,
+        }
+    }
+
+    /// Returns the original location of a preprocessed line.
+    pub fn location(&self, line: usize) -> Option<&'a Location> {
+        self.program.source_map.location(line)
+    }
+}
+
+/// Call stack reconstructed from the tracked calls, since Simplicity has no notion
+/// of function calls.
+///
+/// Reaching a function that is already on the stack returns to it, reaching any other
+/// function calls it from the current one. There are no loops, so a function reaches each
+/// of its tracked calls at most once: reaching one again means the function was entered again.
+#[derive(Default)]
+pub struct CallStack {
+    frames: Vec<Frame>,
+}
+
+struct Frame {
+    function: String,
+    /// Tracked calls reached since the function was entered
+    reached: HashSet<Cmr>,
+}
+
+impl CallStack {
+    /// Records a tracked call in a function, returning whether the function was entered.
+    pub fn track(&mut self, function: &str, cmr: &Cmr) -> bool {
+        let entered = match self.frames.iter().rposition(|f| f.function == function) {
+            Some(index) => {
+                self.frames.truncate(index + 1);
+                let reached = &mut self.frames[index].reached;
+                if reached.contains(cmr) {
+                    reached.clear();
+                    true
+                } else {
+                    false
+                }
+            }
+            None => {
+                self.frames.push(Frame {
+                    function: function.to_string(),
+                    reached: HashSet::new(),
+                });
+                true
+            }
+        };
+        if let Some(frame) = self.frames.last_mut() {
+            frame.reached.insert(*cmr);
+        }
+        entered
+    }
+
+    /// Returns the innermost function.
+    pub fn current(&self) -> Option<&str> {
+        self.frames.last().map(|frame| frame.function.as_str())
+    }
+
+    /// Returns the functions from the outermost to the innermost one.
+    pub fn functions(&self) -> impl Iterator<Item = &str> {
+        self.frames.iter().map(|frame| frame.function.as_str())
+    }
+}
+
+/// Number of bits in a word of the jet buffers
+const UWORD_BITS: usize = 8 * std::mem::size_of::<UWORD>();
+
 #[derive(Debug)]
 pub enum TrackerError {
     UnexpectedAlias(AliasName),
     EndOfBitStream,
-    UnexpectedEndOfBitStream(BitIterCloseError),
+    /// The buffer does not have the number of words that the type requires
+    UnexpectedBufferSize {
+        bit_width: usize,
+        words: usize,
+    },
+    /// The padding after the value contains non-zero bits
+    NonZeroPadding,
     ReconstructError,
     UnexpectedValue(SimValue),
 }
 
-impl From<EarlyEndOfStreamError> for TrackerError {
-    fn from(_: EarlyEndOfStreamError) -> Self {
-        Self::EndOfBitStream
+impl fmt::Display for TrackerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedAlias(alias) => write!(f, "unexpected type alias `{}`", alias),
+            Self::EndOfBitStream => write!(f, "unexpected end of the bit stream"),
+            Self::UnexpectedBufferSize { bit_width, words } => write!(
+                f,
+                "a value of {} bits does not fit exactly into {} words",
+                bit_width, words
+            ),
+            Self::NonZeroPadding => write!(f, "non-zero padding after the value"),
+            Self::ReconstructError => write!(f, "failed to reconstruct the Simfony value"),
+            Self::UnexpectedValue(value) => write!(f, "unexpected value {}", value),
+        }
     }
 }
 
-impl From<BitIterCloseError> for TrackerError {
-    fn from(error: BitIterCloseError) -> Self {
-        Self::UnexpectedEndOfBitStream(error)
+impl From<EarlyEndOfStreamError> for TrackerError {
+    fn from(_: EarlyEndOfStreamError) -> Self {
+        Self::EndOfBitStream
     }
 }
 
-impl<'a> ExecTracker<Elements> for Tracker<'a> {
+impl<'a, W: Write> ExecTracker<Elements> for Tracker<'a, W> {
     fn track_left(&mut self, _: simplicity::Ihr) {}
 
     fn track_right(&mut self, _: simplicity::Ihr) {}
@@ -52,24 +268,31 @@ impl<'a> ExecTracker<Elements> for Tracker<'a> {
         output_buffer: &[UWORD],
         _: bool,
     ) {
-        let args = parse_args(jet, input_buffer).expect("parse args");
-        let result = parse_result(jet, output_buffer).expect("parse res");
-        println!(
-            "{:?}({}) = {}",
-            jet,
-            args.iter().map(ToString::to_string).join(", "),
-            result
-        );
+        let args = match parse_args(jet, input_buffer) {
+            Ok(args) => args.iter().map(ToString::to_string).join(", "),
+            Err(error) => format!("<TrackerError: {}>", error),
+        };
+        let result = match parse_result(jet, output_buffer) {
+            Ok(result) => result.to_string(),
+            Err(error) => format!("<TrackerError: {}>", error),
+        };
+        let _ = writeln!(self.output, "{:?}({}) = {}", jet, args, result);
     }
 
     fn track_dbg_call(&mut self, cmr: &Cmr, value: simplicity::Value) {
         if let Some(tracked_call) = self.debug_symbols.get(cmr) {
-            match tracked_call.map_value(
-                &StructuralValue::from(value),
-            ) {
+            match tracked_call.map_value(&StructuralValue::from(value)) {
                 Some(Either::Right(debug_value)) => {
-                    println!(
-                        "\x1b[1;33mDBG: {} = {}\x1b[0m",
+                    let location = self
+                        .locator
+                        .line(cmr, debug_value.text())
+                        .and_then(|line| self.locator.location(line))
+                        .map(|location| format!(" [{}]", location))
+                        .unwrap_or_default();
+                    let _ = writeln!(
+                        self.output,
+                        "\x1b[1;33mDBG{}: {} = {}\x1b[0m",
+                        location,
                         debug_value.text(),
                         debug_value.value()
                     );
@@ -123,12 +346,25 @@ fn collect_args(
 }
 
 /// Parses a SimValue from an array of words.
+///
+/// The value occupies the first bits of the stream and is followed by
+/// zero padding up to a whole number of words, so `BitIter::close` does not apply.
+/// The Bit Machine passes an empty buffer for a value of zero bits.
 fn parse_sim_value(words: &[UWORD], type_name: TypeName) -> Result<SimValue, TrackerError> {
     let sim_type = type_name.to_final();
+    let bit_width = sim_type.bit_width();
+    if words.len() != bit_width.div_ceil(UWORD_BITS) {
+        return Err(TrackerError::UnexpectedBufferSize {
+            bit_width,
+            words: words.len(),
+        });
+    }
+
     let mut bit_iter = words_into_bit_iter(words);
     let sim_value = SimValue::from_padded_bits(&mut bit_iter, &sim_type)?;
-    // TODO(m-kus): this call fails
-    //bit_iter.close()?;
+    if bit_iter.any(|bit| bit) {
+        return Err(TrackerError::NonZeroPadding);
+    }
     Ok(sim_value)
 }
 
@@ -144,7 +380,7 @@ fn parse_simf_value(
 }
 
 /// Parses the arguments of a jet call.
-fn parse_args(jet: &Elements, words: &[UWORD]) -> Result<Vec<Value>, TrackerError> {
+pub fn parse_args(jet: &Elements, words: &[UWORD]) -> Result<Vec<Value>, TrackerError> {
     let simf_types = source_type(*jet);
     if simf_types.len() == 0 {
         return Ok(vec![]);
@@ -162,8 +398,134 @@ fn parse_args(jet: &Elements, words: &[UWORD]) -> Result<Vec<Value>, TrackerErro
 }
 
 /// Parses the result of a jet call.
-fn parse_result(jet: &Elements, words: &[UWORD]) -> Result<Value, TrackerError> {
+pub fn parse_result(jet: &Elements, words: &[UWORD]) -> Result<Value, TrackerError> {
     let simf_type = target_type(*jet);
     let sim_value = parse_sim_value(words, jet.target_ty())?;
     parse_simf_value(sim_value, &simf_type)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simfony::{CompiledProgram, WitnessValues};
+    use simplicity::types::Final;
+    use simplicity::BitMachine;
+    use std::sync::Arc;
+
+    /// Records the buffers that the Bit Machine passes for each jet call.
+    #[derive(Default)]
+    struct JetBuffers(Vec<(Elements, Vec<UWORD>, Vec<UWORD>)>);
+
+    impl ExecTracker<Elements> for JetBuffers {
+        fn track_left(&mut self, _: simplicity::Ihr) {}
+
+        fn track_right(&mut self, _: simplicity::Ihr) {}
+
+        fn track_jet_call(
+            &mut self,
+            jet: &Elements,
+            input_buffer: &[UWORD],
+            output_buffer: &[UWORD],
+            _: bool,
+        ) {
+            self.0
+                .push((*jet, input_buffer.to_vec(), output_buffer.to_vec()));
+        }
+
+        fn track_dbg_call(&mut self, _: &Cmr, _: simplicity::Value) {}
+    }
+
+    /// Packs the padded bits of a value into words, the inverse of `words_into_bit_iter`.
+    fn value_into_words(value: &SimValue, bit_width: usize) -> Vec<UWORD> {
+        let mut words = vec![0; bit_width.div_ceil(UWORD_BITS)];
+        let len = words.len();
+        for (i, bit) in value.iter_padded().enumerate() {
+            if bit {
+                words[len - 1 - i / UWORD_BITS] |= 1 << (UWORD_BITS - 1 - i % UWORD_BITS);
+            }
+        }
+        words
+    }
+
+    /// Builds a value of a type, choosing the sum branches from the bits of the seed.
+    fn arbitrary_value(ty: &Arc<Final>, seed: &mut u64) -> SimValue {
+        if let Some((left, right)) = ty.as_sum() {
+            *seed = seed.rotate_left(1);
+            if *seed & 1 == 1 {
+                SimValue::right(left.clone(), arbitrary_value(right, seed))
+            } else {
+                SimValue::left(arbitrary_value(left, seed), right.clone())
+            }
+        } else if let Some((left, right)) = ty.as_product() {
+            SimValue::product(arbitrary_value(left, seed), arbitrary_value(right, seed))
+        } else {
+            SimValue::unit()
+        }
+    }
+
+    #[test]
+    fn round_trip_jet_types() {
+        for jet in Elements::ALL {
+            for is_source in [true, false] {
+                let type_name = || match is_source {
+                    true => jet.source_ty(),
+                    false => jet.target_ty(),
+                };
+                let ty = type_name().to_final();
+                for mut seed in [0, u64::MAX, 0x9e37_79b9_7f4a_7c15] {
+                    let value = arbitrary_value(&ty, &mut seed);
+                    let words = value_into_words(&value, ty.bit_width());
+                    let parsed = parse_sim_value(&words, type_name())
+                        .unwrap_or_else(|e| panic!("{:?}: {}", jet, e));
+                    assert_eq!(parsed, value, "{:?}", jet);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reject_invalid_padding() {
+        let ty = Elements::Add32.target_ty().to_final();
+        let value = arbitrary_value(&ty, &mut u64::MAX);
+        let mut words = value_into_words(&value, ty.bit_width());
+        words[0] |= 1;
+        assert!(matches!(
+            parse_sim_value(&words, Elements::Add32.target_ty()),
+            Err(TrackerError::NonZeroPadding)
+        ));
+        words.push(0);
+        assert!(matches!(
+            parse_sim_value(&words, Elements::Add32.target_ty()),
+            Err(TrackerError::UnexpectedBufferSize { .. })
+        ));
+    }
+
+    #[test]
+    fn parse_bit_machine_buffers() {
+        // `version` takes and `verify` (of `assert!`) returns a value of zero bits
+        let source = "\
+fn main() {
+    assert!(jet::le_32(jet::version(), 4294967295));
+}
+";
+        let compiled = CompiledProgram::new(source, simfony::Arguments::default(), false).unwrap();
+        let satisfied = compiled.satisfy(WitnessValues::default()).unwrap();
+        let node = satisfied.redeem();
+        let mut buffers = JetBuffers::default();
+        BitMachine::for_program(node)
+            .unwrap()
+            .exec_with_tracker(node, &simfony::dummy_env::dummy(), &mut buffers)
+            .unwrap();
+
+        let jets = buffers.0.iter().map(|(jet, _, _)| *jet).collect::<Vec<_>>();
+        assert_eq!(jets, [Elements::Version, Elements::Le32, Elements::Verify]);
+        for (jet, input_buffer, output_buffer) in &buffers.0 {
+            parse_args(jet, input_buffer).unwrap_or_else(|e| panic!("{:?}: {}", jet, e));
+            parse_result(jet, output_buffer).unwrap_or_else(|e| panic!("{:?}: {}", jet, e));
+        }
+        let (_, input_buffer, output_buffer) = &buffers.0[2];
+        assert!(output_buffer.is_empty());
+        let args = parse_args(&Elements::Verify, input_buffer).unwrap();
+        assert_eq!(args.iter().map(ToString::to_string).join(", "), "true");
+    }
+}
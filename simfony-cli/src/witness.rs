@@ -0,0 +1,610 @@
+//! Witness and arguments files: the names that a program reads, their types and values.
+
+use std::collections::{BTreeMap, HashSet};
+use std::mem;
+
+use anyhow::{anyhow, Context, Result};
+use simfony::error::WithFile;
+use simfony::parse::ParseFromStr;
+use simfony::str::AliasName;
+use simfony::types::{AliasedType, TypeDeconstructible, TypeInner};
+use simfony::value::StructuralValue;
+use simfony::{ast, parse, CompiledProgram, ResolvedType, Value, WitnessValues};
+use simplicity::dag::{Dag, DagLike};
+use simplicity::jet::Elements;
+use simplicity::node::Inner;
+use simplicity::{BitIter, RedeemNode};
+
+use simfony_cli::source_map::Preprocessed;
+
+/// Names that a program reads from the witness and from the arguments, with their types.
+pub struct Declarations {
+    pub witnesses: BTreeMap<String, ResolvedType>,
+    pub parameters: BTreeMap<String, ResolvedType>,
+}
+
+/// Returns the resolved type of every witness name and parameter of a program, sorted by name.
+pub fn declarations(program: &Preprocessed) -> Result<Declarations> {
+    let rewrite = |error: String| anyhow!(program.source_map.rewrite_error(&error));
+    let parsed =
+        parse::Program::parse_from_str(&program.source).map_err(|e| rewrite(e.to_string()))?;
+    let analyzed = ast::Program::analyze(&parsed)
+        .with_file(program.source.as_str())
+        .map_err(|e| rewrite(e.to_string()))?;
+    Ok(Declarations {
+        witnesses: analyzed
+            .witness_types()
+            .iter()
+            .map(|(name, ty)| (name.to_string(), ty.clone()))
+            .collect(),
+        parameters: analyzed
+            .parameters()
+            .iter()
+            .map(|(name, ty)| (name.to_string(), ty.clone()))
+            .collect(),
+    })
+}
+
+/// Creates a witness file with a placeholder value of the right shape for every witness name.
+pub fn template(types: &BTreeMap<String, ResolvedType>) -> serde_json::Value {
+    witness_file(types, false)
+}
+
+/// Creates a witness file of placeholder values. With markers, the first integer
+/// of the `n`-th witness that can hold `n` is set to it, which tells the witnesses apart.
+fn witness_file(types: &BTreeMap<String, ResolvedType>, markers: bool) -> serde_json::Value {
+    let entries = types
+        .iter()
+        .enumerate()
+        .map(|(index, (name, ty))| {
+            let mut marker = markers.then_some(index as u64 + 1);
+            let entry = serde_json::json!({
+                "value": placeholder(ty, &mut marker),
+                "type": ty.to_string(),
+            });
+            (name.clone(), entry)
+        })
+        .collect();
+    serde_json::Value::Object(entries)
+}
+
+/// Returns the simplest value of a type: zero, `false`, `None`, `Left`, or an empty list.
+/// The marker replaces the first zero that can hold it.
+fn placeholder(ty: &ResolvedType, marker: &mut Option<u64>) -> String {
+    match ty.as_inner() {
+        TypeInner::Boolean => "false".to_string(),
+        TypeInner::UInt(uint) => {
+            let width = uint.bit_width().get();
+            match *marker {
+                Some(value) if width >= 64 || value < 1 << width => {
+                    *marker = None;
+                    value.to_string()
+                }
+                _ => "0".to_string(),
+            }
+        }
+        TypeInner::Option(_) => "None".to_string(),
+        TypeInner::Either(left, _) => format!("Left({})", placeholder(left, marker)),
+        TypeInner::Tuple(elements) => match elements.len() {
+            1 => format!("({},)", placeholder(&elements[0], marker)),
+            _ => format!(
+                "({})",
+                join(elements.iter().map(|ty| placeholder(ty, marker)))
+            ),
+        },
+        TypeInner::Array(_, 0) => "[]".to_string(),
+        TypeInner::Array(element, size) => {
+            let first = placeholder(element, marker);
+            let rest = placeholder(element, &mut None);
+            let elements = std::iter::once(first).chain((1..*size).map(|_| rest.clone()));
+            format!("[{}]", join(elements))
+        }
+        TypeInner::List(..) => "list![]".to_string(),
+    }
+}
+
+fn join(items: impl Iterator<Item = String>) -> String {
+    items.collect::<Vec<_>>().join(", ")
+}
+
+/// Witness values decoded from an encoded witness.
+pub struct Decoded {
+    /// Values of the witnesses that the spending program reads
+    pub values: BTreeMap<String, StructuralValue>,
+    /// Witnesses that were pruned from the spending program
+    pub pruned: Vec<String>,
+}
+
+/// Decodes the witness of an encoded program, e.g. from the script witness of a spending
+/// transaction, into the witness names of the compiled program.
+///
+/// The witness nodes of the encoded program are matched with those of the compiled program
+/// satisfied with marker values. Pruned branches of the encoded program are skipped,
+/// so their witnesses are not recovered.
+pub fn decode(
+    compiled: &CompiledProgram,
+    declared: &BTreeMap<String, ResolvedType>,
+    program_bytes: &[u8],
+    witness_bytes: &[u8],
+) -> Result<Decoded> {
+    let spent = RedeemNode::<Elements>::decode(
+        BitIter::from(program_bytes.iter().copied()),
+        BitIter::from(witness_bytes.iter().copied()),
+    )
+    .map_err(|e| anyhow!("Failed to decode program: {}", e))?;
+    let cmr = compiled.commit().cmr();
+    if spent.cmr() != cmr {
+        return Err(anyhow!(
+            "The encoded program has CMR {}, but the source compiles to {}",
+            spent.cmr(),
+            cmr
+        ));
+    }
+
+    let markers: WitnessValues = serde_json::from_value(witness_file(declared, true))?;
+    let marked = compiled
+        .satisfy(markers.clone())
+        .map_err(|e| anyhow!("Failed to satisfy the program with marker values: {}", e))?;
+    let names: Vec<(String, StructuralValue)> = markers
+        .iter()
+        .map(|(name, value)| (name.to_string(), StructuralValue::from(value)))
+        .collect();
+
+    let mut values = BTreeMap::new();
+    for (marker, value) in match_witness_nodes(marked.redeem(), &spent)? {
+        let marker = StructuralValue::from(marker);
+        let mut matches = names.iter().filter(|(_, value)| *value == marker);
+        let (Some((name, _)), None) = (matches.next(), matches.next()) else {
+            return Err(anyhow!(
+                "Failed to tell the witnesses apart: their types hold no distinct integers"
+            ));
+        };
+        values.insert(name.clone(), StructuralValue::from(value));
+    }
+    let pruned = declared
+        .keys()
+        .filter(|name| !values.contains_key(*name))
+        .cloned()
+        .collect();
+    Ok(Decoded { values, pruned })
+}
+
+impl Decoded {
+    /// Creates a witness file with the decoded values and placeholders for the pruned witnesses.
+    pub fn to_json(&self, declared: &BTreeMap<String, ResolvedType>) -> Result<serde_json::Value> {
+        let mut file = template(declared);
+        for (name, value) in &self.values {
+            let value = Value::reconstruct(value, &declared[name])
+                .ok_or_else(|| anyhow!("Failed to reconstruct the value of witness {}", name))?;
+            file[name]["value"] = value.to_string().into();
+        }
+        Ok(file)
+    }
+
+    /// Compares the decoded values with a witness file, returning the witnesses that differ.
+    pub fn compare(&self, content: &str) -> Result<Vec<String>> {
+        let archived: WitnessValues =
+            serde_json::from_str(content).with_context(|| "Failed to parse witness")?;
+        let mut differences = Vec::new();
+        for (name, value) in archived.iter() {
+            let name = name.to_string();
+            match self.values.get(&name) {
+                Some(decoded) if *decoded == StructuralValue::from(value) => {}
+                Some(_) => differences.push(format!("{}: differs", name)),
+                None if self.pruned.contains(&name) => {}
+                None => differences.push(format!("{}: not declared by the program", name)),
+            }
+        }
+        Ok(differences)
+    }
+}
+
+/// Walks the marked program and the encoded program together, returning the values
+/// of their corresponding witness nodes.
+fn match_witness_nodes<'a>(
+    marked: &'a RedeemNode<Elements>,
+    spent: &'a RedeemNode<Elements>,
+) -> Result<Vec<(simplicity::Value, simplicity::Value)>> {
+    let mismatch = || anyhow!("The encoded program does not match the compiled program");
+    let mut pairs = Vec::new();
+    let mut visited = HashSet::new();
+    let mut stack = vec![(marked, spent)];
+    while let Some((marked, spent)) = stack.pop() {
+        if !visited.insert((marked as *const _, spent as *const _)) {
+            continue;
+        }
+        match (marked.inner(), spent.inner()) {
+            (Inner::Witness(marker), Inner::Witness(value)) => {
+                pairs.push((marker.clone(), value.clone()));
+            }
+            (Inner::Case(left, _), Inner::AssertL(spent_left, _)) => {
+                stack.push((left.as_ref(), spent_left.as_ref()));
+            }
+            (Inner::Case(_, right), Inner::AssertR(_, spent_right)) => {
+                stack.push((right.as_ref(), spent_right.as_ref()));
+            }
+            // The marked program took the other branch of a pruned case
+            (Inner::AssertL(..), Inner::AssertR(..)) => {}
+            (Inner::AssertR(..), Inner::AssertL(..)) => {}
+            (marked_inner, spent_inner) => {
+                if mem::discriminant(marked_inner) != mem::discriminant(spent_inner) {
+                    return Err(mismatch());
+                }
+                match (marked.as_dag_node(), spent.as_dag_node()) {
+                    (Dag::Nullary, Dag::Nullary) => {}
+                    (Dag::Unary(marked), Dag::Unary(spent)) => stack.push((marked, spent)),
+                    (
+                        Dag::Binary(marked_left, marked_right),
+                        Dag::Binary(spent_left, spent_right),
+                    ) => {
+                        stack.push((marked_right, spent_right));
+                        stack.push((marked_left, spent_left));
+                    }
+                    _ => return Err(mismatch()),
+                }
+            }
+        }
+    }
+    Ok(pairs)
+}
+
+/// Checks a witness or arguments file against the declared types, returning every problem
+/// as `<path>: <problem>`, e.g. `FRI_LAYERS[3].1.0: expected u256, got 31-byte hex`.
+pub fn check(content: &str, declared: &BTreeMap<String, ResolvedType>) -> Vec<String> {
+    let entries: serde_json::Map<String, serde_json::Value> = match serde_json::from_str(content) {
+        Ok(entries) => entries,
+        Err(error) => return vec![format!("Invalid JSON: {}", error)],
+    };
+
+    let mut problems: Vec<String> = declared
+        .keys()
+        .filter(|name| !entries.contains_key(*name))
+        .map(|name| format!("{}: missing", name))
+        .collect();
+    for (name, entry) in &entries {
+        let Some(expected) = declared.get(name) else {
+            problems.push(format!("{}: not declared by the program", name));
+            continue;
+        };
+        let (Some(value), Some(ty)) = (entry["value"].as_str(), entry["type"].as_str()) else {
+            problems.push(format!(
+                "{}: expected an object with `value` and `type` strings",
+                name
+            ));
+            continue;
+        };
+        let resolved = AliasedType::parse_from_str(ty)
+            .ok()
+            .and_then(|ty| ty.resolve(|_: &AliasName| None).ok());
+        match resolved {
+            Some(resolved) if resolved == *expected => {}
+            Some(_) => {
+                problems.push(format!(
+                    "{}: declared as {}, but the program expects {}",
+                    name, ty, expected
+                ));
+                continue;
+            }
+            None => {
+                problems.push(format!("{}: invalid type `{}`", name, ty));
+                continue;
+            }
+        }
+        match Parser::parse(value) {
+            Ok(value) => check_value(&value, expected, name, &mut problems),
+            Err(error) => problems.push(format!("{}: {}", name, error)),
+        }
+    }
+    problems
+}
+
+/// Structure of a value expression.
+enum Expr<'a> {
+    /// Number, `true`, `false` or `None`
+    Literal(&'a str),
+    Tuple(Vec<Expr<'a>>),
+    Array(Vec<Expr<'a>>),
+    List(Vec<Expr<'a>>),
+    /// `Left(..)`, `Right(..)` or `Some(..)`
+    Wrapped(&'a str, Box<Expr<'a>>),
+}
+
+impl Expr<'_> {
+    fn describe(&self) -> String {
+        match self {
+            Expr::Literal(literal) => format!("`{}`", literal),
+            Expr::Tuple(elements) => format!("a tuple of {} elements", elements.len()),
+            Expr::Array(elements) => format!("an array of {} elements", elements.len()),
+            Expr::List(elements) => format!("a list of {} elements", elements.len()),
+            Expr::Wrapped(name, _) => format!("`{}(..)`", name),
+        }
+    }
+}
+
+/// Parser of value expressions, which only checks their structure.
+struct Parser<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(text: &'a str) -> Result<Expr<'a>, String> {
+        let mut parser = Self { text, offset: 0 };
+        let expr = parser.expr()?;
+        parser.skip_whitespace();
+        match parser.rest().is_empty() {
+            true => Ok(expr),
+            false => Err(parser.unexpected()),
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.offset..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.offset += rest.len() - rest.trim_start().len();
+    }
+
+    fn unexpected(&self) -> String {
+        match self.rest().chars().next() {
+            Some(c) => format!(
+                "invalid value: unexpected `{}` at offset {}",
+                c, self.offset
+            ),
+            None => "invalid value: unexpected end".to_string(),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr<'a>, String> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        if rest.starts_with("list![") {
+            self.offset += "list![".len();
+            return Ok(Expr::List(self.items(']')?.0));
+        }
+        if rest.starts_with('[') {
+            self.offset += 1;
+            return Ok(Expr::Array(self.items(']')?.0));
+        }
+        if rest.starts_with('(') {
+            self.offset += 1;
+            // A single element without a trailing comma is only parenthesized
+            let (mut elements, trailing_comma) = self.items(')')?;
+            return Ok(match (elements.len(), trailing_comma) {
+                (1, false) => elements.remove(0),
+                _ => Expr::Tuple(elements),
+            });
+        }
+
+        let len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.unexpected());
+        }
+        let word = &rest[..len];
+        self.offset += len;
+        self.skip_whitespace();
+        if matches!(word, "Left" | "Right" | "Some") && self.rest().starts_with('(') {
+            self.offset += 1;
+            let inner = self.expr()?;
+            self.skip_whitespace();
+            if !self.rest().starts_with(')') {
+                return Err(self.unexpected());
+            }
+            self.offset += 1;
+            return Ok(Expr::Wrapped(word, Box::new(inner)));
+        }
+        Ok(Expr::Literal(word))
+    }
+
+    /// Parses comma-separated expressions up to a closing bracket,
+    /// returning them and whether the last one is followed by a comma.
+    fn items(&mut self, close: char) -> Result<(Vec<Expr<'a>>, bool), String> {
+        let mut items = Vec::new();
+        let mut trailing_comma = false;
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with(close) {
+                self.offset += 1;
+                return Ok((items, trailing_comma));
+            }
+            items.push(self.expr()?);
+            self.skip_whitespace();
+            trailing_comma = self.rest().starts_with(',');
+            if trailing_comma {
+                self.offset += 1;
+            } else if !self.rest().starts_with(close) {
+                return Err(self.unexpected());
+            }
+        }
+    }
+}
+
+/// Checks the structure of a value against a type.
+fn check_value(value: &Expr, ty: &ResolvedType, path: &str, problems: &mut Vec<String>) {
+    let mut report =
+        |problem: String| problems.push(format!("{}: expected {}, got {}", path, ty, problem));
+    match (ty.as_inner(), value) {
+        (TypeInner::Boolean, Expr::Literal("true" | "false")) => {}
+        (TypeInner::UInt(uint), Expr::Literal(literal)) => {
+            if let Some(problem) = check_uint(literal, uint.bit_width().get()) {
+                report(problem);
+            }
+        }
+        (TypeInner::Option(_), Expr::Literal("None")) => {}
+        (TypeInner::Option(inner), Expr::Wrapped("Some", value)) => {
+            check_value(value, inner, &format!("{}.Some", path), problems);
+        }
+        (TypeInner::Either(left, _), Expr::Wrapped("Left", value)) => {
+            check_value(value, left, &format!("{}.Left", path), problems);
+        }
+        (TypeInner::Either(_, right), Expr::Wrapped("Right", value)) => {
+            check_value(value, right, &format!("{}.Right", path), problems);
+        }
+        (TypeInner::Tuple(types), Expr::Tuple(values)) if types.len() == values.len() => {
+            for (index, (value, ty)) in values.iter().zip(types.iter()).enumerate() {
+                check_value(value, ty, &format!("{}.{}", path, index), problems);
+            }
+        }
+        (TypeInner::Array(element, size), Expr::Array(values)) if *size == values.len() => {
+            for (index, value) in values.iter().enumerate() {
+                check_value(value, element, &format!("{}[{}]", path, index), problems);
+            }
+        }
+        (TypeInner::List(element, bound), Expr::List(values)) if values.len() < bound.get() => {
+            for (index, value) in values.iter().enumerate() {
+                check_value(value, element, &format!("{}[{}]", path, index), problems);
+            }
+        }
+        _ => report(value.describe()),
+    }
+}
+
+/// Checks an integer literal of a given bit width, describing the literal if it does not fit.
+///
+/// Hexadecimal and binary literals must have exactly as many digits as the width.
+fn check_uint(literal: &str, width: usize) -> Option<String> {
+    if let Some(digits) = literal.strip_prefix("0x") {
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Some(format!("`{}`", literal));
+        }
+        return match digits.len() {
+            len if len * 4 == width => None,
+            len if len % 2 == 0 => Some(format!("{}-byte hex", len / 2)),
+            len => Some(format!("{}-digit hex", len)),
+        };
+    }
+    if let Some(digits) = literal.strip_prefix("0b") {
+        if !digits.chars().all(|c| c == '0' || c == '1') {
+            return Some(format!("`{}`", literal));
+        }
+        return (digits.len() != width).then(|| format!("{}-bit binary", digits.len()));
+    }
+    let digits: String = literal.chars().filter(|c| *c != '_').collect();
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Some(format!("`{}`", literal));
+    }
+    let bits = decimal_bits(&digits);
+    (bits > width).then(|| format!("{}, a {}-bit number", literal, bits))
+}
+
+/// Returns the number of bits of a decimal number of any size.
+fn decimal_bits(digits: &str) -> usize {
+    let mut digits: Vec<u8> = digits.bytes().map(|digit| digit - b'0').collect();
+    let mut bits = 0;
+    loop {
+        let zeros = digits.iter().take_while(|digit| **digit == 0).count();
+        digits.drain(..zeros);
+        if digits.is_empty() {
+            return bits;
+        }
+        // Halve the number, digit by digit
+        let mut remainder = 0;
+        for digit in &mut digits {
+            let current = remainder * 10 + *digit;
+            *digit = current / 2;
+            remainder = current % 2;
+        }
+        bits += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simfony::num::NonZeroPow2Usize;
+    use simfony::types::TypeConstructible;
+
+    fn types() -> BTreeMap<String, ResolvedType> {
+        let qm31 = ResolvedType::tuple([
+            ResolvedType::tuple([ResolvedType::u32(), ResolvedType::u32()]),
+            ResolvedType::tuple([ResolvedType::u32(), ResolvedType::u32()]),
+        ]);
+        let proof = ResolvedType::list(ResolvedType::u256(), NonZeroPow2Usize::new(32).unwrap());
+        let layer = ResolvedType::array(ResolvedType::tuple([qm31, proof]), 2);
+        BTreeMap::from([
+            ("LAYER".to_string(), layer),
+            (
+                "FLAG".to_string(),
+                ResolvedType::either(ResolvedType::boolean(), ResolvedType::u8()),
+            ),
+        ])
+    }
+
+    #[test]
+    fn template_is_a_valid_witness() {
+        let types = types();
+        let template = template(&types);
+        assert_eq!(template["FLAG"]["value"], "Left(false)");
+        assert_eq!(
+            template["LAYER"]["value"],
+            "[(((0, 0), (0, 0)), list![]), (((0, 0), (0, 0)), list![])]"
+        );
+        assert!(check(&template.to_string(), &types).is_empty());
+        serde_json::from_value::<WitnessValues>(template).unwrap();
+    }
+
+    #[test]
+    fn report_problem_paths() {
+        let types = types();
+        let layer = types["LAYER"].to_string();
+        let content = serde_json::json!({
+            "LAYER": {
+                "value": format!(
+                    "[(((0, 0), (0, true)), list![0x{}]), ((0, 0, 0), list![])]",
+                    "00".repeat(31)
+                ),
+                "type": layer,
+            },
+            "EXTRA": { "value": "0", "type": "u8" },
+        });
+        assert_eq!(
+            check(&content.to_string(), &types),
+            [
+                "FLAG: missing",
+                "EXTRA: not declared by the program",
+                "LAYER[0].0.1.1: expected u32, got `true`",
+                "LAYER[0].1[0]: expected u256, got 31-byte hex",
+                "LAYER[1].0: expected ((u32, u32), (u32, u32)), got a tuple of 3 elements",
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_pruned_witness() {
+        let source = "\
+fn main() {
+    let a: u32 = witness::A;
+    match witness::B {
+        Left(x: u8) => assert!(jet::eq_32(a, 1)),
+        Right(y: u16) => assert!(jet::eq_16(y, witness::C)),
+    };
+}
+";
+        let program = Preprocessed {
+            source: source.to_string(),
+            source_map: Default::default(),
+        };
+        let declared = declarations(&program).unwrap().witnesses;
+        let compiled = CompiledProgram::new(source, simfony::Arguments::default(), false).unwrap();
+        let content = r#"{
+            "A": { "value": "1", "type": "u32" },
+            "B": { "value": "Left(7)", "type": "Either<u8, u16>" },
+            "C": { "value": "0", "type": "u16" }
+        }"#;
+        let witness: WitnessValues = serde_json::from_str(content).unwrap();
+        let satisfied = compiled
+            .satisfy_with_env(witness, Some(&simfony::dummy_env::dummy()))
+            .unwrap();
+        let (program_bytes, witness_bytes) = satisfied.redeem().encode_to_vec();
+
+        let decoded = decode(&compiled, &declared, &program_bytes, &witness_bytes).unwrap();
+        assert_eq!(decoded.values.len(), 2);
+        assert_eq!(decoded.pruned, ["C"]);
+        assert!(decoded.compare(content).unwrap().is_empty());
+        assert_eq!(decoded.to_json(&declared).unwrap()["B"]["value"], "Left(7)");
+    }
+}
@@ -0,0 +1,417 @@
+//! Analysis of a document: preprocessing, compiler diagnostics and the declarations.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use simfony::jet::{source_type, target_type};
+use simfony::parse::{ParseFromStr, Program};
+use simfony::str::AliasName;
+use simfony::types::AliasedType;
+use simfony::{ast, ResolvedType, TemplateProgram};
+use simplicity::jet::Elements;
+
+use simfony_cli::preprocessor::Preprocessor;
+use simfony_cli::source_map::{Location, Preprocessed, SourceMap};
+
+use crate::symbols::{SymbolIndex, SymbolKind};
+
+/// An error found in a document or in one of the files it includes.
+pub struct Problem {
+    /// File of the error, if known
+    pub file: Option<PathBuf>,
+    /// 1-based line
+    pub line: usize,
+    /// 0-based column
+    pub column: usize,
+    pub length: usize,
+    pub message: String,
+}
+
+/// Preprocessed document with its declarations.
+pub struct Analysis {
+    pub program: Preprocessed,
+    pub index: SymbolIndex,
+    /// First preprocessed line of each original line, keyed by the canonical file path
+    lines: HashMap<(PathBuf, usize), usize>,
+    /// Type aliases, resolved by the compiler
+    aliases: HashMap<String, ResolvedType>,
+    /// Types of the witness names, if the program could be analyzed
+    witnesses: HashMap<String, ResolvedType>,
+    /// Types of the parameters, if the program could be analyzed
+    parameters: HashMap<String, ResolvedType>,
+}
+
+impl Analysis {
+    /// Preprocesses and compiles a document, returning the analysis if the preprocessing
+    /// succeeds and the errors found.
+    ///
+    /// Files without a `main` function are only parsed, since they are meant to be included.
+    pub fn new(path: &Path, mut preprocessor: Preprocessor) -> (Option<Self>, Vec<Problem>) {
+        let program = match preprocessor.preprocess_file(path) {
+            Ok(program) => program,
+            Err(error) => return (None, vec![preprocessor_problem(&error)]),
+        };
+
+        let index = SymbolIndex::new(&program);
+        let has_main = index
+            .resolve("main", 0)
+            .is_some_and(|symbol| symbol.kind == SymbolKind::Function);
+        let error = if has_main {
+            TemplateProgram::new(program.source.as_str()).err()
+        } else {
+            Program::parse_from_str(&program.source)
+                .err()
+                .map(|error| error.to_string())
+        };
+        let problems = error
+            .map(|error| compiler_problem(&error, &program.source_map))
+            .into_iter()
+            .collect();
+
+        let mut aliases = HashMap::new();
+        for alias in index.aliases() {
+            if let Some(ty) = resolve_type(&alias.detail, &aliases) {
+                aliases.insert(alias.name.clone(), ty);
+            }
+        }
+        let analyzed = has_main
+            .then(|| Program::parse_from_str(&program.source).ok())
+            .flatten()
+            .and_then(|parsed| ast::Program::analyze(&parsed).ok());
+        let (witnesses, parameters) = analyzed
+            .map(|analyzed| {
+                let witnesses = analyzed
+                    .witness_types()
+                    .iter()
+                    .map(|(name, ty)| (name.to_string(), ty.clone()))
+                    .collect();
+                let parameters = analyzed
+                    .parameters()
+                    .iter()
+                    .map(|(name, ty)| (name.to_string(), ty.clone()))
+                    .collect();
+                (witnesses, parameters)
+            })
+            .unwrap_or_default();
+
+        let mut canonical_paths = HashMap::new();
+        let mut lines = HashMap::new();
+        for line in 1..=program.source.lines().count() {
+            let Some(location) = program.source_map.location(line) else {
+                continue;
+            };
+            let file = canonical_paths
+                .entry(location.file.clone())
+                .or_insert_with(|| canonical(&location.file))
+                .clone();
+            lines.entry((file, location.line)).or_insert(line);
+        }
+
+        let analysis = Self {
+            program,
+            index,
+            lines,
+            aliases,
+            witnesses,
+            parameters,
+        };
+        (Some(analysis), problems)
+    }
+
+    /// Returns the preprocessed line of a 1-based line of an original file.
+    pub fn preprocessed_line(&self, path: &Path, line: usize) -> Option<usize> {
+        self.lines.get(&(canonical(path), line)).copied()
+    }
+
+    /// Returns the hover text of a word at a 1-based line of an original file.
+    ///
+    /// Declared types are shown together with the type they resolve to,
+    /// and witness names and parameters with the types the compiler infers for them.
+    pub fn hover(&self, path: &Path, line: usize, word: &Word) -> Option<String> {
+        let declared = |kind: &str, types: &HashMap<String, ResolvedType>| {
+            let ty = types.get(&word.text)?;
+            Some(code_block(&format!("{}::{}: {}", kind, word.text, ty)))
+        };
+        match word.namespace.as_deref() {
+            Some("jet") => {
+                return jet_signature(&word.text).map(|signature| code_block(&signature))
+            }
+            Some("witness") => return declared("witness", &self.witnesses),
+            Some("param") => return declared("param", &self.parameters),
+            _ => {}
+        }
+        let line = self.preprocessed_line(path, line)?;
+        let symbol = self.index.resolve(&word.text, line)?;
+        let (declaration, ty) = match symbol.kind {
+            SymbolKind::Binding => (
+                format!("let {}: {}", symbol.name, symbol.detail),
+                Some(symbol.detail.as_str()),
+            ),
+            SymbolKind::Function => (symbol.detail.clone(), None),
+            SymbolKind::TypeAlias => (
+                format!("type {} = {}", symbol.name, symbol.detail),
+                Some(symbol.detail.as_str()),
+            ),
+        };
+
+        let mut hover = code_block(&declaration);
+        if let Some((ty, resolved)) = ty.and_then(|ty| Some((ty, resolve_type(ty, &self.aliases)?)))
+        {
+            let resolved = resolved.to_string();
+            if resolved != ty {
+                hover.push_str(&format!("\n\n`{} = {}`", ty, resolved));
+            }
+        }
+        Some(hover)
+    }
+
+    /// Returns the original location of the declaration of a word
+    /// at a 1-based line of an original file.
+    pub fn definition(&self, path: &Path, line: usize, word: &Word) -> Option<&Location> {
+        if word.namespace.is_some() {
+            return None;
+        }
+        let line = self.preprocessed_line(path, line)?;
+        let symbol = self.index.resolve(&word.text, line)?;
+        self.program.source_map.location(symbol.line)
+    }
+}
+
+/// An identifier in the source code.
+pub struct Word {
+    pub text: String,
+    /// Namespace that the identifier follows, e.g. `jet` for `jet::add_32`
+    pub namespace: Option<String>,
+}
+
+/// Converts a 0-based column in UTF-16 code units, which LSP positions count,
+/// to a column in chars.
+pub fn char_column(line: &str, utf16_column: usize) -> usize {
+    let mut units = 0;
+    line.chars()
+        .take_while(|c| {
+            units += c.len_utf16();
+            units <= utf16_column
+        })
+        .count()
+}
+
+/// Converts a 0-based column in chars to a column in UTF-16 code units.
+pub fn utf16_column(line: &str, char_column: usize) -> usize {
+    line.chars().take(char_column).map(char::len_utf16).sum()
+}
+
+/// Returns the identifier at a 0-based column (in chars) of a line.
+pub fn word_at(line: &str, column: usize) -> Option<Word> {
+    let chars: Vec<char> = line.chars().collect();
+    let is_ident = |c: &char| c.is_ascii_alphanumeric() || *c == '_';
+    if !chars.get(column).is_some_and(is_ident) {
+        return None;
+    }
+    let start = chars[..column]
+        .iter()
+        .rposition(|c| !is_ident(c))
+        .map_or(0, |index| index + 1);
+    let end = chars[column..]
+        .iter()
+        .position(|c| !is_ident(c))
+        .map_or(chars.len(), |index| column + index);
+    let namespace = chars[..start]
+        .strip_suffix(&[':', ':'])
+        .map(|prefix| {
+            let start = prefix
+                .iter()
+                .rposition(|c| !is_ident(c))
+                .map_or(0, |index| index + 1);
+            prefix[start..].iter().collect::<String>()
+        })
+        .filter(|namespace| !namespace.is_empty());
+    Some(Word {
+        text: chars[start..end].iter().collect(),
+        namespace,
+    })
+}
+
+/// Returns the signature of a jet from its Simfony name.
+fn jet_signature(name: &str) -> Option<String> {
+    let jet = Elements::ALL.iter().find(|jet| jet.to_string() == name)?;
+    let params = source_type(*jet)
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(format!(
+        "fn jet::{}({}) -> {}",
+        name,
+        params,
+        target_type(*jet)
+    ))
+}
+
+/// Parses a type and resolves its aliases, as the compiler does.
+fn resolve_type(ty: &str, aliases: &HashMap<String, ResolvedType>) -> Option<ResolvedType> {
+    AliasedType::parse_from_str(ty)
+        .ok()?
+        .resolve(|alias: &AliasName| aliases.get(&alias.to_string()).cloned())
+        .ok()
+}
+
+fn code_block(code: &str) -> String {
+    format!("```simplicityhl\n{}\n```", code)
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Locates a preprocessor error, which starts with the `file:line` of the directive,
+/// either alone (`file:12`) or followed by the message (`file:12: unexpected #elif`).
+fn preprocessor_problem(error: &anyhow::Error) -> Problem {
+    let (file, line) = match split_location(&error.to_string()) {
+        Some((file, line)) => (Some(file), line),
+        None => (None, 1),
+    };
+    Problem {
+        file,
+        line,
+        column: 0,
+        length: 0,
+        message: format!("{:#}", error),
+    }
+}
+
+/// Splits a leading `file:line` from a message. The file is everything before
+/// the first colon that is followed by a line number, so it may contain colons itself.
+fn split_location(message: &str) -> Option<(PathBuf, usize)> {
+    message.match_indices(':').find_map(|(index, _)| {
+        let (number, _) = message[index + 1..]
+            .split_once(':')
+            .unwrap_or((&message[index + 1..], ""));
+        let line = number.parse().ok()?;
+        Some((PathBuf::from(&message[..index]), line))
+    })
+}
+
+/// Locates a compiler error by its gutter (`12 | let x: u32 = ...`)
+/// and the carets that underline the span, followed by the message.
+fn compiler_problem(error: &str, source_map: &SourceMap) -> Problem {
+    let mut problem = Problem {
+        file: None,
+        line: 1,
+        column: 0,
+        length: 0,
+        message: error.to_string(),
+    };
+    let gutter_line = error.lines().find_map(|line| {
+        let (number, rest) = line.trim_start().split_once(' ')?;
+        rest.starts_with('|').then_some(())?;
+        number.parse::<usize>().ok()
+    });
+    if let Some(location) = gutter_line.and_then(|line| source_map.location(line)) {
+        problem.file = Some(location.file.to_path_buf());
+        problem.line = location.line;
+    }
+    let caret_line = error.lines().find_map(|line| {
+        let (_, underline) = line.split_once('|')?;
+        let column = underline.find('^')?;
+        underline[..column]
+            .trim()
+            .is_empty()
+            .then_some((column, underline))
+    });
+    if let Some((column, underline)) = caret_line {
+        let carets = underline[column..]
+            .chars()
+            .take_while(|c| *c == '^')
+            .count();
+        problem.column = column.saturating_sub(1);
+        problem.length = carets;
+        problem.message = underline[column + carets..].trim().to_string();
+    }
+    problem
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_word_at_column() {
+        let word = word_at("    let (a, b): (u32, u32) = jet::add_32(x, y);", 36).unwrap();
+        assert_eq!(word.text, "add_32");
+        assert_eq!(word.namespace.as_deref(), Some("jet"));
+        let word = word_at("fn fold(layer: FriLayer)", 17).unwrap();
+        assert_eq!(word.text, "FriLayer");
+        assert!(word.namespace.is_none());
+        assert!(word_at("a + b", 1).is_none());
+    }
+
+    #[test]
+    fn convert_utf16_columns() {
+        // `é` takes one UTF-16 code unit and `𝔽` two
+        let line = "/* é𝔽 */ let x: u8 = 0;";
+        assert_eq!(char_column(line, 14), 13);
+        assert_eq!(utf16_column(line, 13), 14);
+        assert_eq!(word_at(line, char_column(line, 14)).unwrap().text, "x");
+    }
+
+    #[test]
+    fn hover_resolved_types() {
+        let path = Path::new("main.simf");
+        let source = "\
+type CM31 = (u32, u32);
+type QM31 = (CM31, CM31);
+fn main() {
+    let x: QM31 = ((1, 2), (3, 4));
+    let w: u8 = witness::W;
+    assert!(jet::eq_8(w, 0));
+}
+";
+        let mut preprocessor = Preprocessor::new(Vec::new());
+        preprocessor.add_overlay(path, source.to_string());
+        let (analysis, _) = Analysis::new(path, preprocessor);
+        let analysis = analysis.unwrap();
+        let hover = |line: usize, word: &str| {
+            let text = source.lines().nth(line - 1).unwrap();
+            let word = word_at(text, text.rfind(word).unwrap()).unwrap();
+            analysis.hover(path, line, &word).unwrap()
+        };
+        assert_eq!(hover(5, "W"), "```simplicityhl\nwitness::W: u8\n```");
+        assert_eq!(hover(6, "w"), "```simplicityhl\nlet w: u8\n```");
+        assert_eq!(
+            hover(4, "x"),
+            "```simplicityhl\nlet x: QM31\n```\n\n`QM31 = ((u32, u32), (u32, u32))`"
+        );
+        assert_eq!(
+            hover(2, "QM31"),
+            "```simplicityhl\ntype QM31 = (CM31, CM31)\n```\n\n\
+             `(CM31, CM31) = ((u32, u32), (u32, u32))`"
+        );
+    }
+
+    #[test]
+    fn locate_preprocessor_errors() {
+        let path = Path::new("main.simf");
+        for (source, line) in [
+            ("fn main() {}\n#else\n", 2),
+            ("#if 1\n#else\n#elif 1\n#endif\n", 3),
+            ("\n#error stop here\n", 2),
+            ("#if 1 +\n#endif\n", 1),
+        ] {
+            let mut preprocessor = Preprocessor::new(Vec::new());
+            preprocessor.add_overlay(path, source.to_string());
+            let (analysis, problems) = Analysis::new(path, preprocessor);
+            assert!(analysis.is_none());
+            assert_eq!(problems[0].file.as_deref(), Some(path), "{}", source);
+            assert_eq!(problems[0].line, line, "{}", source);
+        }
+        assert_eq!(
+            split_location("C:\\src\\main.simf:7: unexpected #endif"),
+            Some((PathBuf::from("C:\\src\\main.simf"), 7))
+        );
+        assert_eq!(
+            split_location("main.simf: unterminated conditional block"),
+            None
+        );
+    }
+}
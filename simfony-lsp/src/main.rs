@@ -0,0 +1,340 @@
+//! Language server for SimplicityHL sources.
+//!
+//! Documents are preprocessed like `simfony build` does, with the include directories
+//! and macros given in the initialization options:
+//! `{"includeDirs": ["src"], "defines": ["TESTING"]}`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+    Notification as _, PublishDiagnostics,
+};
+use lsp_types::request::{GotoDefinition, HoverRequest, Request as _};
+use lsp_types::{
+    Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DidSaveTextDocumentParams, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverParams, HoverProviderCapability,
+    InitializeParams, Location, MarkupContent, MarkupKind, OneOf, Position,
+    PublishDiagnosticsParams, Range, ServerCapabilities, TextDocumentPositionParams,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use simfony_cli::preprocessor::Preprocessor;
+
+use analysis::{char_column, utf16_column, word_at, Analysis, Word};
+
+mod analysis;
+mod symbols;
+
+/// Initialization options of the server
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Config {
+    /// Include directories, relative to the workspace root
+    include_dirs: Vec<PathBuf>,
+    /// Macros in the `NAME[=VALUE]` form
+    defines: Vec<String>,
+}
+
+struct Server {
+    connection: Connection,
+    config: Config,
+    root: Option<PathBuf>,
+    /// Contents of the open documents
+    documents: HashMap<Url, String>,
+    /// Latest analysis of each open document that could be preprocessed
+    analyses: HashMap<Url, Analysis>,
+}
+
+impl Server {
+    fn new(connection: Connection, params: InitializeParams) -> Self {
+        let config = params
+            .initialization_options
+            .and_then(|options| serde_json::from_value(options).ok())
+            .unwrap_or_default();
+        #[allow(deprecated)]
+        let root = params.root_uri.and_then(|uri| uri.to_file_path().ok());
+        Self {
+            connection,
+            config,
+            root,
+            documents: HashMap::new(),
+            analyses: HashMap::new(),
+        }
+    }
+
+    /// Handles the messages until the client shuts the server down.
+    fn run(mut self) -> Result<()> {
+        let receiver = self.connection.receiver.clone();
+        for message in &receiver {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    if let Err(error) = self.handle_request(request) {
+                        eprintln!("Error: {:#}", error);
+                    }
+                }
+                Message::Notification(notification) => {
+                    if let Err(error) = self.handle_notification(notification) {
+                        eprintln!("Error: {:#}", error);
+                    }
+                }
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&mut self, request: Request) -> Result<()> {
+        let response = match request.method.as_str() {
+            HoverRequest::METHOD => {
+                match parse_params::<HoverParams>(&request.id, request.params) {
+                    Ok(params) => {
+                        let hover = self.hover(&params.text_document_position_params);
+                        Response::new_ok(request.id, hover)
+                    }
+                    Err(response) => response,
+                }
+            }
+            GotoDefinition::METHOD => {
+                match parse_params::<GotoDefinitionParams>(&request.id, request.params) {
+                    Ok(params) => {
+                        let definition = self.definition(&params.text_document_position_params);
+                        Response::new_ok(request.id, definition)
+                    }
+                    Err(response) => response,
+                }
+            }
+            method => Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unsupported method: {}", method),
+            ),
+        };
+        self.connection.sender.send(Message::Response(response))?;
+        Ok(())
+    }
+
+    fn handle_notification(&mut self, notification: Notification) -> Result<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let uri = params.text_document.uri;
+                self.documents
+                    .insert(uri.clone(), params.text_document.text);
+                self.analyze(uri)
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let uri = params.text_document.uri;
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.documents.insert(uri.clone(), change.text);
+                }
+                self.analyze(uri)
+            }
+            DidSaveTextDocument::METHOD => {
+                let params: DidSaveTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                self.analyze(params.text_document.uri)
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                self.analyses.remove(&uri);
+                self.publish(uri, Vec::new())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Creates a preprocessor with the configured include directories and macros
+    /// that reads the open documents from memory.
+    fn preprocessor(&self) -> Preprocessor {
+        let include_dirs = self
+            .config
+            .include_dirs
+            .iter()
+            .map(|dir| match &self.root {
+                Some(root) => root.join(dir),
+                None => dir.clone(),
+            })
+            .collect();
+        let mut preprocessor = Preprocessor::new(include_dirs);
+        for define in &self.config.defines {
+            preprocessor.define_arg(define);
+        }
+        for (uri, text) in &self.documents {
+            if let Ok(path) = uri.to_file_path() {
+                preprocessor.add_overlay(&path, text.clone());
+            }
+        }
+        preprocessor
+    }
+
+    /// Analyzes a document and publishes its diagnostics.
+    fn analyze(&mut self, uri: Url) -> Result<()> {
+        let path = uri
+            .to_file_path()
+            .map_err(|_| anyhow!("Not a file: {}", uri))?;
+        let (analysis, problems) = Analysis::new(&path, self.preprocessor());
+        if let Some(analysis) = analysis {
+            self.analyses.insert(uri.clone(), analysis);
+        }
+
+        let canonical = path.canonicalize().unwrap_or(path);
+        let document = self.documents.get(&uri).map_or("", String::as_str);
+        let diagnostics = problems
+            .into_iter()
+            .map(|problem| {
+                let in_document = problem.file.as_ref().is_some_and(|file| {
+                    file.canonicalize().unwrap_or_else(|_| file.clone()) == canonical
+                });
+                // Errors in the included files are shown at the start of the document
+                let (range, message) = match (in_document, &problem.file) {
+                    (true, _) => {
+                        let line = problem.line.saturating_sub(1);
+                        let text = document.lines().nth(line).unwrap_or_default();
+                        let column = |column| utf16_column(text, column) as u32;
+                        let start = Position::new(line as u32, column(problem.column));
+                        let end =
+                            Position::new(start.line, column(problem.column + problem.length));
+                        (Range::new(start, end), problem.message)
+                    }
+                    (false, Some(file)) => (
+                        Range::default(),
+                        format!("{}:{}: {}", file.display(), problem.line, problem.message),
+                    ),
+                    (false, None) => (Range::default(), problem.message),
+                };
+                Diagnostic {
+                    range,
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    source: Some("simfony".to_string()),
+                    message,
+                    ..Diagnostic::default()
+                }
+            })
+            .collect();
+        self.publish(uri, diagnostics)
+    }
+
+    fn publish(&self, uri: Url, diagnostics: Vec<Diagnostic>) -> Result<()> {
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+        let notification = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+        self.connection
+            .sender
+            .send(Message::Notification(notification))?;
+        Ok(())
+    }
+
+    /// Returns the analysis of a document, its path and the word at a position.
+    fn word_at_position(
+        &self,
+        params: &TextDocumentPositionParams,
+    ) -> Option<(&Analysis, PathBuf, Word)> {
+        let uri = &params.text_document.uri;
+        let analysis = self.analyses.get(uri)?;
+        let path = uri.to_file_path().ok()?;
+        let line = self
+            .documents
+            .get(uri)?
+            .lines()
+            .nth(params.position.line as usize)?;
+        let word = word_at(line, char_column(line, params.position.character as usize))?;
+        Some((analysis, path, word))
+    }
+
+    fn hover(&self, params: &TextDocumentPositionParams) -> Option<Hover> {
+        let (analysis, path, word) = self.word_at_position(params)?;
+        let text = analysis.hover(&path, params.position.line as usize + 1, &word)?;
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: text,
+            }),
+            range: None,
+        })
+    }
+
+    fn definition(&self, params: &TextDocumentPositionParams) -> Option<GotoDefinitionResponse> {
+        let (analysis, path, word) = self.word_at_position(params)?;
+        let location = analysis.definition(&path, params.position.line as usize + 1, &word)?;
+        let uri = Url::from_file_path(fs::canonicalize(&location.file).ok()?).ok()?;
+        let line = location.line.saturating_sub(1);
+        let column = self
+            .read_line(&uri, &location.file, line)
+            .and_then(|text| find_word(&text, &word.text))
+            .unwrap_or(0);
+        let position = Position::new(line as u32, column as u32);
+        Some(GotoDefinitionResponse::Scalar(Location::new(
+            uri,
+            Range::new(position, position),
+        )))
+    }
+
+    /// Reads a 0-based line of a file, preferring the open document.
+    fn read_line(&self, uri: &Url, path: &Path, line: usize) -> Option<String> {
+        let text = match self.documents.get(uri) {
+            Some(text) => text.clone(),
+            None => fs::read_to_string(path).ok()?,
+        };
+        text.lines().nth(line).map(str::to_string)
+    }
+}
+
+/// Parses the parameters of a request, or returns the error response to send instead.
+fn parse_params<P: DeserializeOwned>(
+    id: &RequestId,
+    params: serde_json::Value,
+) -> std::result::Result<P, Response> {
+    serde_json::from_value(params).map_err(|error| {
+        Response::new_err(
+            id.clone(),
+            ErrorCode::InvalidParams as i32,
+            format!("Invalid parameters: {}", error),
+        )
+    })
+}
+
+/// Returns the column of the first occurrence of an identifier as a whole word,
+/// in UTF-16 code units.
+fn find_word(line: &str, word: &str) -> Option<usize> {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    line.match_indices(word)
+        .map(|(index, _)| index)
+        .find(|&index| {
+            !line[..index].ends_with(is_ident) && !line[index + word.len()..].starts_with(is_ident)
+        })
+        .map(|index| line[..index].encode_utf16().count())
+}
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    }
+}
+
+fn main() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = serde_json::to_value(capabilities())?;
+    let params: InitializeParams = serde_json::from_value(connection.initialize(capabilities)?)?;
+    Server::new(connection, params).run()?;
+    io_threads.join()?;
+    Ok(())
+}
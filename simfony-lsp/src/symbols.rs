@@ -0,0 +1,280 @@
+//! Index of the declarations in preprocessed source code.
+//!
+//! SimplicityHL requires type annotations on every binding, so the declarations and their
+//! types can be found in the source text of a document that does not compile yet.
+//! The types are resolved by the compiler when the document is analyzed.
+
+use simfony_cli::source_map::{function_at, FunctionSpan, Preprocessed};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    TypeAlias,
+    /// Function parameter or `let` binding
+    Binding,
+}
+
+/// A declaration in the preprocessed source code.
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Signature of a function, aliased type of a type alias or type of a binding
+    pub detail: String,
+    /// 1-based line of the declaration in the preprocessed source code
+    pub line: usize,
+}
+
+/// Declarations of a preprocessed program.
+pub struct SymbolIndex {
+    symbols: Vec<Symbol>,
+    functions: Vec<FunctionSpan>,
+}
+
+impl SymbolIndex {
+    pub fn new(program: &Preprocessed) -> Self {
+        let source = &program.source;
+        let mut symbols = Vec::new();
+        let functions = program.functions();
+
+        for function in &functions {
+            let Some(signature) = statement_at(source, function.start, "fn", '{') else {
+                continue;
+            };
+            let signature = signature.trim_end_matches('{').trim().to_string();
+            if let Some(params) = signature
+                .find('(')
+                .and_then(|open| matching_paren(&signature, open).map(|close| (open, close)))
+                .map(|(open, close)| &signature[open + 1..close])
+            {
+                for param in split_top_level(params, ',') {
+                    if let Some((name, ty)) = param.split_once(':') {
+                        symbols.push(Symbol {
+                            name: name.trim().to_string(),
+                            kind: SymbolKind::Binding,
+                            detail: ty.trim().to_string(),
+                            line: function.start,
+                        });
+                    }
+                }
+            }
+            symbols.push(Symbol {
+                name: function.name.clone(),
+                kind: SymbolKind::Function,
+                detail: signature,
+                line: function.start,
+            });
+        }
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let trimmed = line.trim_start();
+            if keyword_rest(trimmed, "type").is_some() {
+                let Some(statement) = statement_at(source, line_number, "type", ';') else {
+                    continue;
+                };
+                let statement = statement.trim_end_matches(';');
+                if let Some((name, ty)) =
+                    keyword_rest(statement, "type").and_then(|rest| rest.split_once('='))
+                {
+                    symbols.push(Symbol {
+                        name: name.trim().to_string(),
+                        kind: SymbolKind::TypeAlias,
+                        detail: ty.trim().to_string(),
+                        line: line_number,
+                    });
+                }
+            }
+            if let Some(rest) = trimmed
+                .find("let ")
+                .filter(|&pos| pos == 0 || !is_ident(trimmed.as_bytes()[pos - 1]))
+                .and_then(|pos| keyword_rest(&trimmed[pos..], "let"))
+            {
+                for (name, ty) in let_bindings(rest) {
+                    symbols.push(Symbol {
+                        name,
+                        kind: SymbolKind::Binding,
+                        detail: ty,
+                        line: line_number,
+                    });
+                }
+            }
+        }
+
+        Self { symbols, functions }
+    }
+
+    /// Finds the declaration that a name refers to at a line of the preprocessed source code:
+    /// the last binding of the enclosing function before the line, or else a function
+    /// or a type alias.
+    pub fn resolve(&self, name: &str, line: usize) -> Option<&Symbol> {
+        let binding = function_at(&self.functions, line).and_then(|function| {
+            self.symbols.iter().rev().find(|symbol| {
+                symbol.kind == SymbolKind::Binding
+                    && symbol.name == name
+                    && function.start <= symbol.line
+                    && symbol.line <= line
+            })
+        });
+        binding.or_else(|| {
+            self.symbols
+                .iter()
+                .find(|symbol| symbol.kind != SymbolKind::Binding && symbol.name == name)
+        })
+    }
+
+    /// Returns the type aliases in the order of their declarations.
+    pub fn aliases(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols
+            .iter()
+            .filter(|symbol| symbol.kind == SymbolKind::TypeAlias)
+    }
+}
+
+fn is_ident(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+/// Returns the text after a keyword at the start of the text.
+fn keyword_rest<'a>(text: &'a str, keyword: &str) -> Option<&'a str> {
+    let rest = text.strip_prefix(keyword)?;
+    rest.starts_with(char::is_whitespace).then_some(rest)
+}
+
+/// Returns the text from a keyword at a 1-based line up to and including a terminator,
+/// which may span several lines.
+fn statement_at(source: &str, line: usize, keyword: &str, terminator: char) -> Option<String> {
+    let offset: usize = source
+        .split_inclusive('\n')
+        .take(line - 1)
+        .map(str::len)
+        .sum();
+    let rest = &source[offset..];
+    let start = rest.find(keyword)?;
+    let end = rest[start..].find(terminator)?;
+    Some(rest[start..start + end + 1].to_string())
+}
+
+/// Returns the index of the parenthesis that closes the one at `open`.
+fn matching_paren(text: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (index, c) in text.char_indices().skip_while(|(i, _)| *i < open) {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits a text at the separators outside of parentheses, brackets and angle brackets.
+fn split_top_level(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        match c {
+            '(' | '[' | '<' => depth += 1,
+            ')' | ']' | '>' => depth -= 1,
+            _ if c == separator && depth == 0 => {
+                parts.push(text[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(text[start..].trim());
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+/// Returns the names and types bound by the rest of a `let` statement:
+/// `x: T = ...` or `(a, b): (A, B) = ...`.
+fn let_bindings(rest: &str) -> Vec<(String, String)> {
+    let rest = rest.split('=').next().unwrap_or_default();
+    let Some((pattern, ty)) = split_pattern(rest) else {
+        return Vec::new();
+    };
+    let (pattern, ty) = (pattern.trim(), ty.trim());
+    match (tuple_items(pattern), tuple_items(ty)) {
+        (Some(names), Some(types)) if names.len() == types.len() => names
+            .into_iter()
+            .zip(types)
+            .filter(|(name, _)| *name != "_")
+            .map(|(name, ty)| (name.to_string(), ty.to_string()))
+            .collect(),
+        (None, _) if pattern != "_" => vec![(pattern.to_string(), ty.to_string())],
+        _ => Vec::new(),
+    }
+}
+
+/// Returns the items of a parenthesized tuple.
+fn tuple_items(text: &str) -> Option<Vec<&str>> {
+    text.strip_prefix('(')
+        .and_then(|text| text.strip_suffix(')'))
+        .map(|text| split_top_level(text, ','))
+}
+
+/// Splits a `let` binding at the colon that separates the pattern from the type.
+fn split_pattern(text: &str) -> Option<(&str, &str)> {
+    let mut depth = 0i32;
+    for (index, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ':' if depth == 0 => return Some((&text[..index], &text[index + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn resolve_declarations() {
+        let source = "\
+type QM31 = (CM31, CM31);
+fn add(a: QM31, b: QM31) -> QM31 {
+    let (x, _): (CM31, CM31) = a;
+    let y: QM31 = b;
+    y
+}
+fn main() {
+    let y: u32 = 1;
+    assert!(jet::eq_32(y, 1));
+}
+";
+        let program = Preprocessed::single_file(Path::new("main.simf"), source);
+        let index = SymbolIndex::new(&program);
+
+        let y = index.resolve("y", 5).unwrap();
+        assert_eq!(
+            (y.kind, y.detail.as_str(), y.line),
+            (SymbolKind::Binding, "QM31", 4)
+        );
+        let y = index.resolve("y", 9).unwrap();
+        assert_eq!((y.detail.as_str(), y.line), ("u32", 8));
+        assert_eq!(index.resolve("x", 5).unwrap().detail, "CM31");
+        assert_eq!(index.resolve("a", 3).unwrap().detail, "QM31");
+        assert_eq!(
+            index.resolve("add", 9).unwrap().detail,
+            "fn add(a: QM31, b: QM31) -> QM31"
+        );
+        let aliases = index
+            .aliases()
+            .map(|symbol| (symbol.name.as_str(), symbol.detail.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(aliases, [("QM31", "(CM31, CM31)")]);
+        assert!(index.resolve("a", 9).is_none());
+    }
+}
@@ -2,11 +2,9 @@ use std::path::Path;
 
 use anyhow::anyhow;
 use elements::secp256k1_zkp as secp256k1;
-use elements::{
-    taproot::{LeafVersion, TaprootBuilder, TaprootSpendInfo},
-    Address, AddressParams, Script,
-};
+use elements::{Address, AddressParams};
 use simfony::{Arguments, CompiledProgram, WitnessValues};
+use simfony_cli::taproot;
 
 /// Load Simfony program from .simf file and compile it to a Simplicity program
 pub fn load_program(path: &Path) -> anyhow::Result<CompiledProgram> {
@@ -16,12 +14,6 @@ pub fn load_program(path: &Path) -> anyhow::Result<CompiledProgram> {
     Ok(compiled)
 }
 
-/// Create a Bitcoin script from a compiled Simplicity program
-pub fn create_script(program: &CompiledProgram) -> anyhow::Result<Script> {
-    let script = Script::from(program.commit().cmr().as_ref().to_vec());
-    Ok(script)
-}
-
 /// Generate a (non-confidential) P2TR address from a Simfony program and a key pair
 pub fn create_p2tr_address(
     program: CompiledProgram,
@@ -29,8 +21,7 @@ pub fn create_p2tr_address(
 ) -> anyhow::Result<Address> {
     let (x_only_public_key, _) = key_pair.x_only_public_key();
 
-    let script = create_script(&program)?;
-    let spend_info = taproot_spending_info(script, x_only_public_key)?;
+    let spend_info = taproot::spend_info(program.commit().cmr(), x_only_public_key)?;
 
     let address = Address::p2tr(
         secp256k1::SECP256K1,
@@ -42,11 +33,6 @@ pub fn create_p2tr_address(
     Ok(address)
 }
 
-/// Taproot leaf version for Simplicity (Simfony) programs
-pub fn simplicity_leaf_version() -> LeafVersion {
-    LeafVersion::from_u8(0xbe).expect("constant leaf version")
-}
-
 /// Parse a .wit file into a WitnessValues struct
 pub fn parse_witness(path: &Path) -> anyhow::Result<WitnessValues> {
     let witness_bytes = std::fs::read(path)?;
@@ -54,21 +40,3 @@ pub fn parse_witness(path: &Path) -> anyhow::Result<WitnessValues> {
         .map_err(|e| anyhow!("Failed to parse witness: {}", e))?;
     Ok(witness)
 }
-
-/// Create a TaprootSpendInfo struct for a given Simfony program and public key
-pub fn taproot_spending_info(
-    script: Script,
-    public_key: secp256k1::XOnlyPublicKey,
-) -> anyhow::Result<TaprootSpendInfo> {
-    let builder = TaprootBuilder::new();
-    let version = simplicity_leaf_version();
-
-    let builder = builder
-        .add_leaf_with_ver(0, script, version)
-        .map_err(|e| anyhow!("Failed to add leaf to taproot builder: {}", e))?;
-
-    let spend_info = builder
-        .finalize(&secp256k1::SECP256K1, public_key)
-        .map_err(|e| anyhow!("Failed to finalize taproot builder: {}", e))?;
-    Ok(spend_info)
-}